@@ -14,6 +14,7 @@ use serde::Serialize;
 use serde_json::Value as JsonValue;
 use uuid::Uuid;
 
+use snowplow_tracker::payload::EventType;
 use snowplow_tracker::{HasSchema, Platform, Schema, SchemaVersion, TrackedEvent, Tracker};
 
 #[derive(clap::Parser)]
@@ -52,7 +53,8 @@ async fn main() -> anyhow::Result<()> {
             .parse()
             .expect("hardcoded URL"),
         reqwest::Client::new(),
-    );
+    )
+    .context("Failed to create snowplow tracker")?;
 
     let event_id = Uuid::new_v4();
 
@@ -64,6 +66,10 @@ async fn main() -> anyhow::Result<()> {
             },
             id: Some(event_id),
             timestamp: None,
+            sent_timestamp: None,
+            contexts: Vec::new(),
+            app_id: None,
+            event_type: EventType::SelfDescribingEvent,
         })
         .await
         .context("Failed to send snowplow event")?;
@@ -14,37 +14,38 @@ A snowplow event [`Emitter`]. This type manages the low-level details of sending
 events over HTTP to a Collector. Generally you should prefer to use a
 [`Tracker`][crate::tracker::Tracker], which wraps an [`Emitter`] handles a lot
 of the bookkeeping required to construct full snowplow events.
+
+This module also contains a [`FileEmitter`], which writes events to a local
+file instead, for debugging, and (with the `uds` feature, on unix) a
+`UnixSocketEmitter`, which sends them to a collector over a Unix domain
+socket.
  */
 
+use std::fmt;
+use std::fs::{File, OpenOptions};
 use std::future::ready;
+use std::io::{self, Write as _};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::{Arc, PoisonError};
 
 use futures::TryStreamExt as _;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::redirect::Policy;
+#[cfg(any(feature = "default-tls", feature = "rustls"))]
+use reqwest::tls;
 use reqwest::Client;
 
 use serde::Serialize;
+use thiserror::Error;
+use tokio::sync::Mutex;
 use url::Url;
 
-use crate::payload::{Envelope, HasSchema, Schema, SchemaVersion, SnowplowEvent};
-
-/// The outermost type that is actually sent to snowplow as a JSON payload.
-/// Includes an outermost schema and a Vec of [`SnowplowEvent`].
-// TODO: It will be exceedingly common to only need to send a single event;
-// create an optimized version of this type to handle that use case.
-type EventContainer<'a, Payload> = Envelope<Vec<SnowplowEvent<'a, Payload>>>;
-
-impl<'a, Payload: HasSchema> EventContainer<'a, Payload> {
-    /// Create a new event container. This will collect all of the given events
-    /// into a [`Vec`].
-    pub fn new(events: impl IntoIterator<Item = SnowplowEvent<'a, Payload>>) -> Self {
-        Envelope(events.into_iter().collect())
-    }
-}
-
-impl<'a, Payload: HasSchema> HasSchema for Vec<SnowplowEvent<'a, Payload>> {
-    fn schema(&self) -> Schema {
-        Schema::new_snowplow("payload_data", SchemaVersion::new(1, 0, 4))
-    }
-}
+use crate::payload::{EventContainer, HasSchema, SnowplowEvent};
+use crate::sink::EventSink;
+use crate::tracker::TrackError;
 
 /// Emitter is responsible for emitting tracked events to the Snowplow
 /// Collector. It takes care of the low-level HTTP stuff. You should probably
@@ -52,46 +53,1003 @@ impl<'a, Payload: HasSchema> HasSchema for Vec<SnowplowEvent<'a, Payload>> {
 pub struct Emitter {
     collector_url: Url,
     client: Client,
+
+    /// If present, this lock is held for the duration of each request, so
+    /// that batches are delivered one at a time in the order they were sent.
+    send_lock: Option<Mutex<()>>,
+
+    header_provider: Option<HeaderProvider>,
+
+    /// If present, each request has a header with this name, containing the
+    /// number of events in the batch.
+    batch_size_header: Option<HeaderName>,
 }
 
+/// A function that supplies extra headers for each request. See
+/// [`EmitterBuilder::header_provider`].
+type HeaderProvider = Box<dyn Fn() -> HeaderMap + Send + Sync>;
+
 impl Emitter {
     /// Create a new emitter that will send events to the given Url using the
     /// given client.
+    ///
+    /// The client is used as-is, so it gets none of the defaults of
+    /// [`Emitter::builder`]: redirects aren't disabled, the minimum TLS
+    /// version isn't raised, and an `http://` URL isn't upgraded. A default
+    /// `reqwest::Client` follows redirects, and a redirected POST loses its
+    /// body, so the events are silently dropped; prefer the builder unless
+    /// you need a custom client.
     pub const fn new(collector_url: Url, client: Client) -> Emitter {
         // TODO: log a warning if the Url doesn't look right
         Emitter {
             collector_url,
             client,
+            send_lock: None,
+            header_provider: None,
+            batch_size_header: None,
         }
     }
 
+    /// Create an [`EmitterBuilder`], for configuring an emitter that will
+    /// send events to the given Url. By default, the emitter doesn't follow
+    /// redirects, requires at least TLS 1.2, and in release builds upgrades
+    /// an `http://` URL to `https://`.
+    pub fn builder(collector_url: Url) -> EmitterBuilder {
+        let client_builder = Client::builder()
+            .redirect(Policy::none())
+            .dns_resolver(Arc::new(DnsResolver));
+
+        #[cfg(any(feature = "default-tls", feature = "rustls"))]
+        let client_builder = client_builder.min_tls_version(tls::Version::TLS_1_2);
+
+        #[cfg(feature = "rustls")]
+        let client_builder = client_builder.use_rustls_tls();
+
+        EmitterBuilder {
+            collector_url,
+            client_builder,
+            ordered_delivery: false,
+            api_key: None,
+            header_provider: None,
+            batch_size_header: None,
+            upgrade_to_https: cfg!(not(debug_assertions)),
+        }
+    }
+
+    /// The URL of the collector that this emitter sends events to.
+    pub fn collector_url(&self) -> &Url {
+        &self.collector_url
+    }
+
+    /// The HTTP client that this emitter sends events with. This can be
+    /// cloned cheaply to make other requests, like warm-up requests, with the
+    /// same TLS, proxy and timeout settings, without building a second
+    /// client. Note that requests made with it also send any default headers
+    /// configured on the builder, like the [API key][EmitterBuilder::api_key].
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
     /// Track a batch of events, sending them to the snowplow collector
     pub async fn track_events<Payload: HasSchema + Serialize>(
         &self,
         events: impl IntoIterator<Item = SnowplowEvent<'_, Payload>>,
-    ) -> Result<(), reqwest::Error> {
-        let events = EventContainer::new(events);
-
-        let response = self
-            .client
-            .post(self.collector_url.clone())
-            .json(&events)
-            .send()
-            .await?;
+    ) -> Result<(), TrackError> {
+        self.send(&EventContainer::new(events)).await
+    }
+
+    /// Track a single event
+    pub async fn track_event<Payload: HasSchema + Serialize>(
+        &self,
+        event: SnowplowEvent<'_, Payload>,
+    ) -> Result<(), TrackError> {
+        self.track_events([event]).await
+    }
+
+    /// Check if the collector is reachable, by requesting its `/health`
+    /// endpoint. Returns true if it responded with `200 OK`. This is useful for
+    /// failing fast on a misconfigured collector URL at startup. Any timeout
+    /// configured on the HTTP client applies to this request.
+    pub async fn health_check(&self) -> bool {
+        let mut url = self.collector_url.clone();
+        url.set_path("/health");
+        url.set_query(None);
+
+        match self.client.get(url).send().await {
+            Ok(response) => response.status() == reqwest::StatusCode::OK,
+            Err(err) => {
+                tracing::warn!(error = %err, "Snowplow collector health check failed");
+                false
+            }
+        }
+    }
+
+    async fn send<Payload: HasSchema + Serialize>(
+        &self,
+        events: &EventContainer<'_, Payload>,
+    ) -> Result<(), TrackError> {
+        // With ordered delivery, hold the lock until the response has been
+        // fully drained, so the next batch can't start before this one ends.
+        let _guard = match &self.send_lock {
+            Some(lock) => Some(lock.lock().await),
+            None => None,
+        };
+
+        let mut request = self.client.post(self.collector_url.clone()).json(events);
+        if let Some(name) = &self.batch_size_header {
+            request = request.header(name, events.0.len());
+        }
+        if let Some(provider) = &self.header_provider {
+            request = request.headers(provider());
+        }
+
+        let response = request.send().await?;
+
+        // A redirect that wasn't followed means the events never reached the
+        // collector, so it must not be mistaken for a success.
+        if response.status().is_redirection() {
+            return Err(TrackError::Redirected {
+                status: response.status(),
+                location: response
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .map(str::to_owned),
+            });
+        }
 
         // Snowplow responses don't contain anything useful, so just drain the
         // response content.
         response
             .bytes_stream()
             .try_for_each(|_chunk| ready(Ok(())))
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl EventSink for Emitter {
+    async fn emit<Payload: HasSchema + Serialize + Send + Sync>(
+        &self,
+        batch: EventContainer<'_, Payload>,
+    ) -> Result<(), TrackError> {
+        self.send(&batch).await
+    }
+}
+
+/// Resolves host names with the system resolver, like the HTTP client does by
+/// default, but wraps failures in a [`DnsError`], so that they can be
+/// reported as [`ConnectErrorKind::Dns`][crate::ConnectErrorKind::Dns].
+struct DnsResolver;
+
+impl Resolve for DnsResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_owned();
+        Box::pin(async move {
+            // The port is replaced by the collector URL's
+            let addrs = tokio::net::lookup_host((host, 0)).await.map_err(DnsError)?;
+            Ok(Box::new(addrs) as Addrs)
+        })
+    }
+}
+
+/// The host name of the collector couldn't be resolved.
+#[derive(Debug, Error)]
+#[error("Failed to resolve the collector's host name")]
+pub(crate) struct DnsError(#[source] io::Error);
+
+/// Builder for an [`Emitter`] with non-default settings. Create one with
+/// [`Emitter::builder`].
+pub struct EmitterBuilder {
+    collector_url: Url,
+    client_builder: reqwest::ClientBuilder,
+    ordered_delivery: bool,
+    api_key: Option<HeaderValue>,
+    header_provider: Option<HeaderProvider>,
+    batch_size_header: Option<HeaderName>,
+    upgrade_to_https: bool,
+}
+
+impl fmt::Debug for EmitterBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmitterBuilder")
+            .field("collector_url", &self.collector_url)
+            .field("client_builder", &self.client_builder)
+            .field("ordered_delivery", &self.ordered_delivery)
+            .field("api_key", &self.api_key)
+            .field("batch_size_header", &self.batch_size_header)
+            .field("upgrade_to_https", &self.upgrade_to_https)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EmitterBuilder {
+    /// If enabled, batches are delivered strictly one at a time: a batch
+    /// isn't sent until every batch tracked before it has finished sending,
+    /// even if they're tracked concurrently. This preserves ordering for
+    /// pipelines that depend on it, at the cost of throughput. Disabled by
+    /// default.
+    pub fn ordered_delivery(mut self, enabled: bool) -> Self {
+        self.ordered_delivery = enabled;
+        self
+    }
+
+    /// Send the given API key in an `X-API-Key` header with every request,
+    /// for collectors or gateways that require one. The key is marked as
+    /// sensitive, so it's redacted from the `Debug` output of the builder and
+    /// of any requests.
+    ///
+    /// ```
+    /// use reqwest::header::HeaderValue;
+    /// use snowplow_tracker::emitter::Emitter;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let api_key = "secret-api-key";
+    /// let builder = Emitter::builder("https://collector.example.com".parse()?)
+    ///     .api_key(HeaderValue::from_str(api_key)?);
+    ///
+    /// assert!(!format!("{builder:?}").contains(api_key));
+    /// builder.build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn api_key(mut self, mut key: HeaderValue) -> Self {
+        key.set_sensitive(true);
+        self.api_key = Some(key);
+        self
+    }
+
+    /// Register a function that supplies extra headers for each request. The
+    /// function is called right before every request is sent, so it can
+    /// supply credentials that change over time, like a freshly signed,
+    /// short-lived `Authorization` token. Its headers are added after the
+    /// default ones, replacing any with the same name.
+    pub fn header_provider(
+        mut self,
+        provider: impl Fn() -> HeaderMap + Send + Sync + 'static,
+    ) -> Self {
+        self.header_provider = Some(Box::new(provider));
+        self
+    }
+
+    /// Send the number of events in each batch in a header with the given
+    /// name, like `X-Batch-Size`, for collector deployments that log or route
+    /// requests based on it. Disabled by default.
+    pub fn batch_size_header(mut self, name: HeaderName) -> Self {
+        self.batch_size_header = Some(name);
+        self
+    }
+
+    /// If enabled, an `http://` collector URL is upgraded to `https://`, and a
+    /// warning is logged, so that events aren't accidentally tracked in
+    /// plaintext. Enabled by default in release builds and disabled in debug
+    /// builds; disable it explicitly to use a plain HTTP collector in
+    /// production, or enable it to test the upgrade locally.
+    pub fn upgrade_to_https(mut self, enabled: bool) -> Self {
+        self.upgrade_to_https = enabled;
+        self
+    }
+
+    /// Set the redirect policy of the HTTP client. By default, redirects are
+    /// not followed, and a redirect response is reported as a
+    /// [`TrackError::Redirected`] error.
+    ///
+    /// Following redirects is dangerous for tracking: when a POST request is
+    /// redirected with a 301, 302 or 303 status, it's retried as a GET
+    /// request *without its body*. The collector then reports success, but
+    /// the events are silently lost. You should generally fix the collector
+    /// URL instead of following redirects.
+    pub fn redirect(mut self, policy: Policy) -> Self {
+        self.client_builder = self.client_builder.redirect(policy);
+        self
+    }
+
+    /// Set the minimum TLS version that the client will accept when
+    /// connecting to the collector; connections that can't negotiate at
+    /// least this version are rejected. Defaults to TLS 1.2.
+    ///
+    /// With the default native TLS backend, building the emitter fails if the
+    /// platform's TLS library doesn't support the requested version, which is
+    /// generally the case for TLS 1.3. Enable the `rustls` feature to use
+    /// TLS 1.3.
+    ///
+    /// Requires a TLS backend: the `default-tls` feature, which is enabled by
+    /// default, or the `rustls` feature.
+    #[cfg(any(feature = "default-tls", feature = "rustls"))]
+    pub fn min_tls_version(mut self, version: tls::Version) -> Self {
+        self.client_builder = self.client_builder.min_tls_version(version);
+        self
+    }
+
+    /// Send requests from the given local IP address, for hosts with several
+    /// network interfaces where tracking must leave from a specific one. By
+    /// default, the operating system chooses the address.
+    pub fn local_address(mut self, address: IpAddr) -> Self {
+        self.client_builder = self.client_builder.local_address(address);
+        self
+    }
+
+    /// Only use HTTP/2, without first negotiating it with the collector. This
+    /// saves the upgrade round-trip, but only works if the collector is known
+    /// to support HTTP/2. By default, the protocol is negotiated.
+    pub fn http2_prior_knowledge(mut self) -> Self {
+        self.client_builder = self.client_builder.http2_prior_knowledge();
+        self
+    }
+
+    /// Build the [`Emitter`]. This fails if the underlying HTTP client can't
+    /// be created.
+    pub fn build(self) -> Result<Emitter, reqwest::Error> {
+        let mut client_builder = self.client_builder;
+        let mut collector_url = self.collector_url;
+
+        if self.upgrade_to_https && collector_url.scheme() == "http" {
+            tracing::warn!(
+                url = %collector_url,
+                "Upgrading plaintext Snowplow collector URL to https"
+            );
+            collector_url
+                .set_scheme("https")
+                .expect("http URLs can always be changed to https");
+        }
+
+        if let Some(api_key) = self.api_key {
+            let mut headers = HeaderMap::new();
+            headers.insert("X-API-Key", api_key);
+            client_builder = client_builder.default_headers(headers);
+        }
+
+        // Collector responses are tiny, but an intermediary may compress them
+        // anyway; with this feature the client accepts and decodes that.
+        #[cfg(feature = "compression")]
+        let client_builder = client_builder.gzip(true).brotli(true);
+
+        Ok(Emitter {
+            collector_url,
+            client: client_builder.build()?,
+            send_lock: self.ordered_delivery.then(|| Mutex::new(())),
+            header_provider: self.header_provider,
+            batch_size_header: self.batch_size_header,
+        })
+    }
+}
+
+/// An [`EventSink`] that appends each batch of events to a local file instead
+/// of sending it to a collector. Each batch is written as a single line of
+/// JSON, containing exactly the `payload_data` envelope that would have been
+/// sent to a collector, so the file is in JSON Lines format. This is intended
+/// for capturing and inspecting what your application produces while
+/// debugging.
+///
+/// Each batch is serialized, then written and flushed on tokio's blocking
+/// thread pool, so file I/O doesn't stall the async runtime. This requires a
+/// tokio runtime.
+#[derive(Debug)]
+pub struct FileEmitter {
+    file: Arc<std::sync::Mutex<File>>,
+}
+
+impl FileEmitter {
+    /// Create a new file emitter that appends to the file at the given path,
+    /// creating it if it doesn't exist.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            file: Arc::new(std::sync::Mutex::new(file)),
+        })
+    }
+}
+
+impl EventSink for FileEmitter {
+    async fn emit<Payload: HasSchema + Serialize + Send + Sync>(
+        &self,
+        batch: EventContainer<'_, Payload>,
+    ) -> Result<(), TrackError> {
+        let mut line = serde_json::to_vec(&batch).map_err(|err| TrackError::Sink(err.into()))?;
+        line.push(b'\n');
+
+        let file = Arc::clone(&self.file);
+        tokio::task::spawn_blocking(move || {
+            let mut file = file.lock().unwrap_or_else(PoisonError::into_inner);
+            file.write_all(&line).and_then(|()| file.flush())
+        })
+        .await
+        .map_err(|err| TrackError::Sink(err.into()))?
+        .map_err(|err| TrackError::Sink(err.into()))
+    }
+}
+
+/// An [`EventSink`] that sends events over HTTP to a collector listening on a
+/// Unix domain socket, rather than on a TCP port. This is useful for sidecar
+/// deployments. Requires the `uds` feature, and is only available on unix.
+///
+/// The request is a plain POST of the batch as JSON, like the one an
+/// [`Emitter`] sends by default, but none of the [`EmitterBuilder`] options
+/// are supported: there is no API key, header provider, batch size header or
+/// ordered delivery, and no timeout, so wrap the call to `track` in
+/// `tokio::time::timeout` if the collector might hang. As with an
+/// [`Emitter`], failing to connect to the socket is reported as
+/// [`TrackError::Connect`], and a redirect as [`TrackError::Redirected`];
+/// other failures, like a malformed response, are reported as
+/// [`TrackError::Sink`].
+#[cfg(all(unix, feature = "uds"))]
+#[derive(Debug, Clone)]
+pub struct UnixSocketEmitter {
+    socket_path: std::path::PathBuf,
+    request_path: String,
+}
+
+#[cfg(all(unix, feature = "uds"))]
+impl UnixSocketEmitter {
+    /// Create a new emitter that connects to the socket at `socket_path`, and
+    /// sends events with a POST request to `request_path`, which is usually
+    /// `/com.snowplowanalytics.snowplow/tp2`.
+    pub fn new(
+        socket_path: impl Into<std::path::PathBuf>,
+        request_path: impl Into<String>,
+    ) -> Self {
+        Self {
+            socket_path: socket_path.into(),
+            request_path: request_path.into(),
+        }
+    }
+
+    async fn send(&self, body: Vec<u8>) -> Result<(), TrackError> {
+        fn sink_error(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> TrackError {
+            TrackError::Sink(err.into())
+        }
+
+        let stream = tokio::net::UnixStream::connect(&self.socket_path)
+            .await
+            .map_err(|err| TrackError::Connect {
+                kind: crate::tracker::ConnectErrorKind::classify(&err),
+                source: err.into(),
+            })?;
+        let (mut sender, connection) = hyper::client::conn::handshake(stream)
             .await
+            .map_err(sink_error)?;
+
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                tracing::debug!(error = %err, "Unix socket collector connection failed");
+            }
+        });
+
+        let request = hyper::Request::post(self.request_path.as_str())
+            .header(hyper::header::HOST, "localhost")
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(hyper::Body::from(body))
+            .map_err(sink_error)?;
+
+        let response = sender.send_request(request).await.map_err(sink_error)?;
+
+        // As with `Emitter`, a redirect means the events never reached the
+        // collector.
+        if response.status().is_redirection() {
+            return Err(TrackError::Redirected {
+                status: response.status(),
+                location: response
+                    .headers()
+                    .get(hyper::header::LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .map(str::to_owned),
+            });
+        }
+
+        // Snowplow responses don't contain anything useful, so just drain the
+        // response content.
+        hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(sink_error)?;
+        Ok(())
     }
+}
 
-    /// Track a single event
-    pub async fn track_event<Payload: HasSchema + Serialize>(
+#[cfg(all(unix, feature = "uds"))]
+impl EventSink for UnixSocketEmitter {
+    async fn emit<Payload: HasSchema + Serialize + Send + Sync>(
         &self,
-        event: SnowplowEvent<'_, Payload>,
-    ) -> Result<(), reqwest::Error> {
-        self.track_events([event]).await
+        batch: EventContainer<'_, Payload>,
+    ) -> Result<(), TrackError> {
+        let body = serde_json::to_vec(&batch).map_err(|err| TrackError::Sink(err.into()))?;
+        self.send(body).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tokio::sync::Notify;
+
+    use super::*;
+    use crate::payload::SnowplowTimestamp;
+    use crate::testing::{self, MockCollector, MockResponse, TestEvent};
+    use crate::tracker::{ConnectErrorKind, TrackedEvent, TrackerConfig};
+
+    fn events(config: &TrackerConfig, count: u32) -> Vec<SnowplowEvent<'_, TestEvent>> {
+        (0..count)
+            .map(|id| {
+                TrackedEvent::new(TestEvent { id })
+                    .into_snowplow_event(config, SnowplowTimestamp::now())
+            })
+            .collect()
+    }
+
+    fn builder(collector: &MockCollector) -> EmitterBuilder {
+        Emitter::builder(collector.url()).upgrade_to_https(false)
+    }
+
+    #[tokio::test]
+    async fn posts_batch_to_collector() {
+        let collector = MockCollector::start(MockResponse::ok()).await;
+        let emitter = builder(&collector).build().unwrap();
+        let config = testing::config();
+
+        emitter.track_events(events(&config, 2)).await.unwrap();
+
+        let requests = collector.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/com.snowplowanalytics.snowplow/tp2");
+        assert_eq!(requests[0].header("content-type"), Some("application/json"));
+
+        let body = requests[0].json();
+        assert_eq!(
+            body["schema"],
+            "iglu:com.snowplowanalytics.snowplow/payload_data/jsonschema/1-0-4"
+        );
+        assert_eq!(body["data"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn redirect_is_an_error() {
+        let collector = MockCollector::start(
+            MockResponse::status(302).header("location", "http://127.0.0.1:1/elsewhere"),
+        )
+        .await;
+        let emitter = builder(&collector).build().unwrap();
+        let config = testing::config();
+
+        let err = emitter.track_events(events(&config, 1)).await.unwrap_err();
+        match err {
+            TrackError::Redirected { status, location } => {
+                assert_eq!(status, reqwest::StatusCode::FOUND);
+                assert_eq!(location.as_deref(), Some("http://127.0.0.1:1/elsewhere"));
+            }
+            err => panic!("expected a redirect error, got {err:?}"),
+        }
+
+        // The redirect wasn't followed, so only the original request was made
+        assert_eq!(collector.requests().len(), 1);
+    }
+
+    async fn connect_error_kind(url: &str) -> ConnectErrorKind {
+        let emitter = Emitter::builder(url.parse().unwrap())
+            .upgrade_to_https(false)
+            .build()
+            .unwrap();
+        let config = testing::config();
+
+        match emitter.track_events(events(&config, 1)).await.unwrap_err() {
+            TrackError::Connect { kind, .. } => kind,
+            err => panic!("expected a connection error, got {err:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn closed_port_is_refused() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let kind = connect_error_kind(&format!("http://{address}/")).await;
+        assert_eq!(kind, ConnectErrorKind::Refused);
+    }
+
+    #[tokio::test]
+    async fn unresolvable_host_is_dns() {
+        let kind = connect_error_kind("http://collector.nonexistent.invalid/").await;
+        assert_eq!(kind, ConnectErrorKind::Dns);
+    }
+
+    #[cfg(any(feature = "default-tls", feature = "rustls"))]
+    #[tokio::test]
+    async fn failed_handshake_is_tls() {
+        use tokio::io::AsyncWriteExt as _;
+
+        // A server that answers the TLS handshake with plaintext HTTP
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let _ = stream
+                    .write_all(b"HTTP/1.1 400 Bad Request\r\ncontent-length: 0\r\n\r\n")
+                    .await;
+            }
+        });
+
+        let kind = connect_error_kind(&format!("https://{address}/")).await;
+        assert_eq!(kind, ConnectErrorKind::Tls);
+    }
+
+    #[tokio::test]
+    async fn health_check() {
+        let healthy = MockCollector::start(MockResponse::ok()).await;
+        let emitter = builder(&healthy).build().unwrap();
+        assert!(emitter.health_check().await);
+        assert_eq!(healthy.requests()[0].method, "GET");
+        assert_eq!(healthy.requests()[0].path, "/health");
+
+        let unhealthy = MockCollector::start(MockResponse::status(503)).await;
+        assert!(!builder(&unhealthy).build().unwrap().health_check().await);
+
+        // Nothing listens on port 1
+        let unreachable = Emitter::builder("http://127.0.0.1:1/".parse().unwrap())
+            .build()
+            .unwrap();
+        assert!(!unreachable.health_check().await);
+    }
+
+    #[tokio::test]
+    async fn ordered_delivery_waits_for_previous_batch() {
+        /// Send two batches, holding the response to the first until the
+        /// collector has received `wait_for` requests, and return whether
+        /// the first response had been released when the second request
+        /// arrived.
+        async fn second_arrived_after_release(ordered: bool, wait_for: usize) -> bool {
+            let release = Arc::new(Notify::new());
+            let released = Arc::new(AtomicBool::new(false));
+            let (arrived, mut arrivals) = tokio::sync::mpsc::unbounded_channel();
+
+            let collector = MockCollector::with_handler({
+                let release = Arc::clone(&release);
+                let released = Arc::clone(&released);
+                let count = AtomicUsize::new(0);
+                move |_| {
+                    let index = count.fetch_add(1, Ordering::SeqCst);
+                    arrived.send(released.load(Ordering::SeqCst)).unwrap();
+                    match index {
+                        0 => MockResponse::ok().wait_for(Arc::clone(&release)),
+                        _ => MockResponse::ok(),
+                    }
+                }
+            })
+            .await;
+
+            let emitter = builder(&collector)
+                .ordered_delivery(ordered)
+                .build()
+                .unwrap();
+            let config = testing::config();
+
+            // The branches are polled in order, so the second batch is
+            // waiting to be sent before the first is released.
+            let release_first = async {
+                let mut seen = Vec::new();
+                for _ in 0..wait_for {
+                    seen.push(arrivals.recv().await.unwrap());
+                }
+                released.store(true, Ordering::SeqCst);
+                release.notify_one();
+                seen
+            };
+            let (first, second, mut seen) = tokio::join!(
+                emitter.track_events(events(&config, 1)),
+                emitter.track_events(events(&config, 1)),
+                release_first,
+            );
+            first.unwrap();
+            second.unwrap();
+
+            while seen.len() < 2 {
+                seen.push(arrivals.recv().await.unwrap());
+            }
+            seen[1]
+        }
+
+        // With ordered delivery, the second batch is only sent once the first
+        // has been answered; without it, both are in flight at once.
+        assert!(second_arrived_after_release(true, 1).await);
+        assert!(!second_arrived_after_release(false, 2).await);
+    }
+
+    #[test]
+    fn upgrade_to_https() {
+        let url: Url = "http://collector.example.com/tp2".parse().unwrap();
+
+        let upgraded = Emitter::builder(url.clone())
+            .upgrade_to_https(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            upgraded.collector_url().as_str(),
+            "https://collector.example.com/tp2"
+        );
+
+        let plain = Emitter::builder(url)
+            .upgrade_to_https(false)
+            .build()
+            .unwrap();
+        assert_eq!(
+            plain.collector_url().as_str(),
+            "http://collector.example.com/tp2"
+        );
+    }
+
+    #[tokio::test]
+    async fn api_key_header() {
+        let collector = MockCollector::start(MockResponse::ok()).await;
+        let builder = builder(&collector).api_key(HeaderValue::from_static("secret-key"));
+        assert!(!format!("{builder:?}").contains("secret-key"));
+
+        let emitter = builder.build().unwrap();
+        let config = testing::config();
+        emitter.track_events(events(&config, 1)).await.unwrap();
+        assert_eq!(
+            collector.requests()[0].header("x-api-key"),
+            Some("secret-key")
+        );
+    }
+
+    #[tokio::test]
+    async fn api_key_is_not_logged() {
+        let key = HeaderValue::from_static("secret-key");
+        let (_guard, warnings) = testing::capture_warnings();
+
+        let collector = MockCollector::start(MockResponse::ok()).await;
+        let emitter = builder(&collector).api_key(key.clone()).build().unwrap();
+        let tracker = crate::Tracker::new(&emitter, testing::config()).unwrap();
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .unwrap();
+        assert_eq!(
+            collector.requests()[0].header("x-api-key"),
+            Some("secret-key")
+        );
+
+        // A failing send, logged the way an application would log it
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        let emitter = Emitter::builder(format!("http://{address}/").parse().unwrap())
+            .api_key(key)
+            .build()
+            .unwrap();
+        let tracker = crate::Tracker::new(&emitter, testing::config()).unwrap();
+        let err = tracker
+            .track(TrackedEvent::new(TestEvent { id: 2 }))
+            .await
+            .unwrap_err();
+        tracing::warn!(error = ?err, display = %err, "Failed to track event");
+        assert!(!emitter.health_check().await);
+
+        let output = warnings.output();
+        assert!(output.contains("Failed to track event"), "{output}");
+        assert!(!output.contains("secret-key"), "{output}");
+    }
+
+    #[tokio::test]
+    async fn client_shares_configured_defaults() {
+        let collector = MockCollector::start(MockResponse::ok()).await;
+        let emitter = builder(&collector)
+            .api_key(HeaderValue::from_static("secret-key"))
+            .build()
+            .unwrap();
+
+        emitter.client().get(collector.url()).send().await.unwrap();
+        assert_eq!(
+            collector.requests()[0].header("x-api-key"),
+            Some("secret-key")
+        );
+    }
+
+    #[tokio::test]
+    async fn header_provider_is_called_for_each_request() {
+        let collector = MockCollector::start(MockResponse::ok()).await;
+        let token = AtomicUsize::new(0);
+        let emitter = builder(&collector)
+            .header_provider(move || {
+                let token = token.fetch_add(1, Ordering::SeqCst);
+                let mut headers = HeaderMap::new();
+                headers.insert(
+                    reqwest::header::AUTHORIZATION,
+                    format!("Bearer token-{token}").parse().unwrap(),
+                );
+                headers
+            })
+            .build()
+            .unwrap();
+        let config = testing::config();
+
+        emitter.track_events(events(&config, 1)).await.unwrap();
+        emitter.track_events(events(&config, 1)).await.unwrap();
+
+        let requests = collector.requests();
+        assert_eq!(requests[0].header("authorization"), Some("Bearer token-0"));
+        assert_eq!(requests[1].header("authorization"), Some("Bearer token-1"));
+    }
+
+    #[tokio::test]
+    async fn batch_size_header() {
+        let collector = MockCollector::start(MockResponse::ok()).await;
+        let emitter = builder(&collector)
+            .batch_size_header(HeaderName::from_static("x-batch-size"))
+            .build()
+            .unwrap();
+        let config = testing::config();
+
+        emitter.track_events(events(&config, 5)).await.unwrap();
+        emitter.track_events(events(&config, 2)).await.unwrap();
+
+        let requests = collector.requests();
+        assert_eq!(requests[0].header("x-batch-size"), Some("5"));
+        assert_eq!(requests[1].header("x-batch-size"), Some("2"));
+    }
+
+    #[tokio::test]
+    async fn client_options_build() {
+        let collector = MockCollector::start(MockResponse::ok()).await;
+        let config = testing::config();
+
+        #[cfg(any(feature = "default-tls", feature = "rustls"))]
+        let builder = builder(&collector).min_tls_version(tls::Version::TLS_1_2);
+        #[cfg(not(any(feature = "default-tls", feature = "rustls")))]
+        let builder = builder(&collector);
+
+        let emitter = builder
+            .local_address(IpAddr::from([127, 0, 0, 1]))
+            .build()
+            .unwrap();
+        emitter.track_events(events(&config, 1)).await.unwrap();
+        assert_eq!(collector.requests().len(), 1);
+
+        Emitter::builder(collector.url())
+            .http2_prior_knowledge()
+            .build()
+            .unwrap();
+    }
+
+    #[cfg(feature = "rustls")]
+    #[test]
+    fn tls_1_3_minimum_builds_with_rustls() {
+        Emitter::builder("https://collector.example.com".parse().unwrap())
+            .min_tls_version(tls::Version::TLS_1_3)
+            .build()
+            .unwrap();
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn gzipped_response_is_drained() {
+        // "ok", gzipped
+        const BODY: [u8; 22] = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xcb, 0xcf, 0x06, 0x00,
+            0x47, 0xdd, 0xdc, 0x79, 0x02, 0x00, 0x00, 0x00,
+        ];
+
+        let collector = MockCollector::start(MockResponse {
+            body: BODY.to_vec(),
+            ..MockResponse::ok().header("content-encoding", "gzip")
+        })
+        .await;
+        let emitter = builder(&collector).build().unwrap();
+        let config = testing::config();
+
+        emitter.track_events(events(&config, 1)).await.unwrap();
+        assert!(collector.requests()[0]
+            .header("accept-encoding")
+            .is_some_and(|encoding| encoding.contains("gzip")));
+    }
+
+    #[cfg(all(unix, feature = "uds"))]
+    #[tokio::test]
+    async fn unix_socket_round_trip() {
+        let path = testing::temp_path("round-trip.sock");
+        let collector = MockCollector::start_unix(&path, MockResponse::ok());
+        let emitter = UnixSocketEmitter::new(&path, collector.url().path());
+        let config = testing::config();
+
+        emitter
+            .emit(EventContainer::new(events(&config, 2)))
+            .await
+            .unwrap();
+
+        let requests = collector.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/com.snowplowanalytics.snowplow/tp2");
+        assert_eq!(requests[0].header("content-type"), Some("application/json"));
+        assert_eq!(requests[0].json()["data"].as_array().unwrap().len(), 2);
+    }
+
+    #[cfg(all(unix, feature = "uds"))]
+    #[tokio::test]
+    async fn unix_socket_redirect_is_an_error() {
+        let path = testing::temp_path("redirect.sock");
+        let collector = MockCollector::start_unix(
+            &path,
+            MockResponse::status(301).header("location", "/elsewhere"),
+        );
+        let emitter = UnixSocketEmitter::new(&path, collector.url().path());
+        let config = testing::config();
+
+        let err = emitter
+            .emit(EventContainer::new(events(&config, 1)))
+            .await
+            .unwrap_err();
+        match err {
+            TrackError::Redirected { status, location } => {
+                assert_eq!(status, reqwest::StatusCode::MOVED_PERMANENTLY);
+                assert_eq!(location.as_deref(), Some("/elsewhere"));
+            }
+            err => panic!("expected a redirect error, got {err:?}"),
+        }
+    }
+
+    #[cfg(all(unix, feature = "uds"))]
+    #[tokio::test]
+    async fn unix_socket_connect_errors() {
+        async fn connect_error(path: &std::path::Path) -> ConnectErrorKind {
+            let emitter = UnixSocketEmitter::new(path, "/com.snowplowanalytics.snowplow/tp2");
+            let config = testing::config();
+            match emitter
+                .emit(EventContainer::new(events(&config, 1)))
+                .await
+                .unwrap_err()
+            {
+                TrackError::Connect { kind, .. } => kind,
+                err => panic!("expected a connect error, got {err:?}"),
+            }
+        }
+
+        let missing = testing::temp_path("missing.sock");
+        assert_eq!(connect_error(&missing).await, ConnectErrorKind::Other);
+
+        // The socket file outlives its listener, which refuses connections
+        let closed = testing::temp_path("closed.sock");
+        drop(std::os::unix::net::UnixListener::bind(&closed).unwrap());
+        assert_eq!(connect_error(&closed).await, ConnectErrorKind::Refused);
+        let _ = std::fs::remove_file(&closed);
+    }
+
+    #[tokio::test]
+    async fn file_emitter_writes_json_lines() {
+        let path = testing::temp_path("events.jsonl");
+        let emitter = FileEmitter::create(&path).unwrap();
+        let tracker = crate::Tracker::new(&emitter, testing::config()).unwrap();
+
+        tracker
+            .track_batch((0..2).map(|id| TrackedEvent::new(TestEvent { id })))
+            .await
+            .unwrap();
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 2 }))
+            .await
+            .unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let batches: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(batches.len(), 2);
+
+        let ids: Vec<_> = batches
+            .iter()
+            .flat_map(|batch| batch["data"].as_array().unwrap())
+            .map(|event| {
+                testing::unstruct_data(event)["data"]["id"]
+                    .as_u64()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(ids, [0, 1, 2]);
     }
 }
@@ -0,0 +1,252 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+Typed versions of the standard Snowplow context entities. Entities aren't
+tracked on their own; instead they're attached to an event to describe its
+circumstances. Convert them with
+[`SelfDescribingJson::from_entity`][crate::payload::SelfDescribingJson::from_entity]
+and add them to the `contexts` of a
+[`TrackedEvent`][crate::tracker::TrackedEvent].
+*/
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::payload::{HasSchema, Schema, SchemaVersion, SelfDescribingJson};
+
+/// A product involved in an ecommerce event. Corresponds to the
+/// `iglu:com.snowplowanalytics.snowplow.ecommerce/product/jsonschema/1-0-0`
+/// schema.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ProductEntity {
+    /// The SKU or product ID
+    pub id: String,
+
+    /// The name of the product
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// The category the product belongs to
+    pub category: String,
+
+    /// The price of the product at the time of the event
+    #[serde(with = "rust_decimal::serde::float")]
+    pub price: Decimal,
+
+    /// The currency that `price` is expressed in, like `"USD"`. If empty,
+    /// the tracker's
+    /// [`default_currency`][crate::TrackerConfig::default_currency] is used.
+    pub currency: String,
+}
+
+impl HasSchema for ProductEntity {
+    fn schema(&self) -> Schema {
+        Schema::new(
+            "com.snowplowanalytics.snowplow.ecommerce",
+            "product",
+            SchemaVersion::new(1, 0, 0),
+        )
+    }
+}
+
+/// The state of a shopping cart at the time of an ecommerce event.
+/// Corresponds to the
+/// `iglu:com.snowplowanalytics.snowplow.ecommerce/cart/jsonschema/1-0-0`
+/// schema.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CartEntity {
+    /// An identifier for the cart
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cart_id: Option<String>,
+
+    /// The total value of the cart
+    #[serde(with = "rust_decimal::serde::float")]
+    pub total_value: Decimal,
+
+    /// The currency that `total_value` is expressed in, like `"USD"`. If empty,
+    /// the tracker's
+    /// [`default_currency`][crate::TrackerConfig::default_currency] is used.
+    pub currency: String,
+}
+
+impl HasSchema for CartEntity {
+    fn schema(&self) -> Schema {
+        Schema::new(
+            "com.snowplowanalytics.snowplow.ecommerce",
+            "cart",
+            SchemaVersion::new(1, 0, 0),
+        )
+    }
+}
+
+/// A description of the runtime environment of the application: the
+/// operating system, CPU architecture and version of this tracker. This is
+/// mostly useful for server-side and desktop tracking.
+///
+/// There is no standard Iglu Central schema for this entity, so you'll need
+/// to host a schema for it in your own Iglu registry, with the string fields
+/// `os`, `arch` and `trackerVersion`. It can then be attached to every event
+/// with [`TrackerConfig::runtime_context_schema`][crate::TrackerConfig::runtime_context_schema].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeContext {
+    /// The operating system, like `"linux"` or `"macos"`. See
+    /// [`std::env::consts::OS`].
+    pub os: &'static str,
+
+    /// The CPU architecture, like `"x86_64"` or `"aarch64"`. See
+    /// [`std::env::consts::ARCH`].
+    pub arch: &'static str,
+
+    /// The version of this tracker crate
+    pub tracker_version: &'static str,
+}
+
+impl RuntimeContext {
+    /// Detect the runtime environment of the current process.
+    pub fn detect() -> Self {
+        Self {
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            tracker_version: env!("CARGO_PKG_VERSION"),
+        }
+    }
+
+    /// Convert this entity to a context, using the given schema.
+    pub fn to_context(&self, schema: Schema) -> SelfDescribingJson {
+        SelfDescribingJson::new(
+            schema,
+            serde_json::json!({
+                "os": self.os,
+                "arch": self.arch,
+                "trackerVersion": self.tracker_version,
+            }),
+        )
+    }
+}
+
+/// A description of the specific instance of the application that tracked an
+/// event: the host name of the machine and the ID of the process. This helps
+/// trace events to a particular instance in clustered deployments.
+///
+/// Like [`RuntimeContext`], there is no standard Iglu Central schema for this
+/// entity, so you'll need to host a schema for it in your own Iglu registry,
+/// with the string field `hostname` and the integer field `pid`. It can then
+/// be attached to every event with
+/// [`TrackerConfig::instance_context_schema`][crate::TrackerConfig::instance_context_schema].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstanceContext {
+    /// The host name of the machine. If it isn't valid unicode, invalid
+    /// characters are replaced with U+FFFD.
+    pub hostname: String,
+
+    /// The ID of the current process. See [`std::process::id`].
+    pub pid: u32,
+}
+
+impl InstanceContext {
+    /// Detect the host name and process ID of the current process.
+    pub fn detect() -> Self {
+        Self {
+            hostname: gethostname::gethostname().to_string_lossy().into_owned(),
+            pid: std::process::id(),
+        }
+    }
+
+    /// Convert this entity to a context, using the given schema.
+    pub fn to_context(&self, schema: Schema) -> SelfDescribingJson {
+        SelfDescribingJson::new(
+            schema,
+            serde_json::json!({
+                "hostname": self.hostname,
+                "pid": self.pid,
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn product_entity_serializes_all_fields() {
+        let product = ProductEntity {
+            id: "sku-1".to_owned(),
+            name: Some("Hat".to_owned()),
+            category: "Clothing".to_owned(),
+            price: Decimal::new(1250, 2),
+            currency: "USD".to_owned(),
+        };
+
+        let context = SelfDescribingJson::from_entity(&product).unwrap();
+        assert_eq!(
+            serde_json::to_value(&context).unwrap(),
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow.ecommerce/product/jsonschema/1-0-0",
+                "data": {
+                    "id": "sku-1",
+                    "name": "Hat",
+                    "category": "Clothing",
+                    "price": 12.5,
+                    "currency": "USD",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn cart_entity_omits_missing_id() {
+        let cart = CartEntity {
+            cart_id: None,
+            total_value: Decimal::new(30, 0),
+            currency: "EUR".to_owned(),
+        };
+
+        assert_eq!(
+            cart.schema().to_string(),
+            "iglu:com.snowplowanalytics.snowplow.ecommerce/cart/jsonschema/1-0-0"
+        );
+        assert_eq!(
+            serde_json::to_value(&cart).unwrap(),
+            json!({"total_value": 30.0, "currency": "EUR"})
+        );
+    }
+
+    #[test]
+    fn runtime_context_detects_platform() {
+        let runtime = RuntimeContext::detect();
+        assert_eq!(runtime.os, std::env::consts::OS);
+        assert_eq!(runtime.arch, std::env::consts::ARCH);
+        assert!(!runtime.os.is_empty());
+        assert!(!runtime.arch.is_empty());
+
+        let schema = Schema::new("com.example", "runtime", SchemaVersion::new(1, 0, 0));
+        let context = runtime.to_context(schema.clone());
+        assert_eq!(context.schema, schema);
+        assert_eq!(context.data["trackerVersion"], env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn instance_context_detects_host_and_pid() {
+        let instance = InstanceContext::detect();
+        assert!(!instance.hostname.is_empty());
+        assert_eq!(instance.pid, std::process::id());
+
+        let schema = Schema::new("com.example", "instance", SchemaVersion::new(1, 0, 0));
+        let context = instance.to_context(schema);
+        assert_eq!(context.data["hostname"], instance.hostname.as_str());
+        assert_eq!(context.data["pid"], instance.pid);
+    }
+}
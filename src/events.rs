@@ -0,0 +1,485 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+Typed versions of the standard Snowplow self-describing events. Each type in
+this module implements [`HasSchema`] with its Iglu Central schema, so it can
+be used as the payload of a [`TrackedEvent`][crate::tracker::TrackedEvent]
+like any of your own event types.
+*/
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::payload::{HasSchema, Schema, SchemaVersion};
+
+/// A product was added to a shopping cart. Corresponds to the
+/// `iglu:com.snowplowanalytics.snowplow/add_to_cart/jsonschema/1-0-0` schema.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddToCartEvent {
+    /// The SKU of the product added to the cart
+    pub sku: String,
+
+    /// The name of the product
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// The category of the product
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+
+    /// The price of a single unit of the product
+    #[serde(with = "rust_decimal::serde::float")]
+    pub unit_price: Decimal,
+
+    /// How many units were added to the cart
+    pub quantity: u32,
+
+    /// The currency that `unit_price` is expressed in, like `"USD"`. If
+    /// omitted, the tracker's
+    /// [`default_currency`][crate::TrackerConfig::default_currency] is used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+}
+
+impl HasSchema for AddToCartEvent {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("add_to_cart", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// A product was removed from a shopping cart. Corresponds to the
+/// `iglu:com.snowplowanalytics.snowplow/remove_from_cart/jsonschema/1-0-0`
+/// schema.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveFromCartEvent {
+    /// The SKU of the product removed from the cart
+    pub sku: String,
+
+    /// The name of the product
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// The category of the product
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+
+    /// The price of a single unit of the product
+    #[serde(with = "rust_decimal::serde::float")]
+    pub unit_price: Decimal,
+
+    /// How many units were removed from the cart
+    pub quantity: u32,
+
+    /// The currency that `unit_price` is expressed in, like `"USD"`. If
+    /// omitted, the tracker's
+    /// [`default_currency`][crate::TrackerConfig::default_currency] is used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+}
+
+impl HasSchema for RemoveFromCartEvent {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("remove_from_cart", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// A user interacted with a social network, like liking or sharing some
+/// content. Corresponds to the
+/// `iglu:com.snowplowanalytics.snowplow/social_interaction/jsonschema/1-0-0`
+/// schema.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SocialInteractionEvent {
+    /// The social action performed, like `"like"` or `"retweet"`
+    pub action: String,
+
+    /// The social network the action was performed on, like `"facebook"`
+    pub network: String,
+
+    /// The object of the action, like the ID of the shared content
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+impl HasSchema for SocialInteractionEvent {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("social_interaction", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// The pricing model used for an ad, which determines what the `cost` of an
+/// ad event refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CostModel {
+    /// Cost per acquisition
+    #[serde(rename = "cpa")]
+    PerAcquisition,
+
+    /// Cost per click
+    #[serde(rename = "cpc")]
+    PerClick,
+
+    /// Cost per thousand impressions
+    #[serde(rename = "cpm")]
+    PerMille,
+}
+
+/// An ad was shown to a user. Corresponds to the
+/// `iglu:com.snowplowanalytics.snowplow/ad_impression/jsonschema/1-0-0`
+/// schema. All fields are optional.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdImpressionEvent {
+    /// An identifier for this specific ad impression
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub impression_id: Option<String>,
+
+    /// The pricing model for this ad. This should be set if `cost` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_model: Option<CostModel>,
+
+    /// The cost of this ad, according to the `cost_model`
+    #[serde(with = "rust_decimal::serde::float_option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<Decimal>,
+
+    /// The destination URL of the ad
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_url: Option<String>,
+
+    /// An identifier for the ad banner being displayed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub banner_id: Option<String>,
+
+    /// An identifier for the zone where the ad is located
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zone_id: Option<String>,
+
+    /// An identifier for the advertiser of the ad
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub advertiser_id: Option<String>,
+
+    /// An identifier for the ad campaign this ad belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub campaign_id: Option<String>,
+}
+
+impl HasSchema for AdImpressionEvent {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("ad_impression", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// A user clicked on an ad. Corresponds to the
+/// `iglu:com.snowplowanalytics.snowplow/ad_click/jsonschema/1-0-0` schema.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdClickEvent {
+    /// The destination URL of the ad
+    pub target_url: String,
+
+    /// An identifier for this specific click
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub click_id: Option<String>,
+
+    /// An identifier for the impression of the ad that was clicked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub impression_id: Option<String>,
+
+    /// The pricing model for this ad. This should be set if `cost` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_model: Option<CostModel>,
+
+    /// The cost of this ad, according to the `cost_model`
+    #[serde(with = "rust_decimal::serde::float_option")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<Decimal>,
+
+    /// An identifier for the ad banner that was clicked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub banner_id: Option<String>,
+
+    /// An identifier for the zone where the ad is located
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zone_id: Option<String>,
+
+    /// An identifier for the advertiser of the ad
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub advertiser_id: Option<String>,
+
+    /// An identifier for the ad campaign this ad belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub campaign_id: Option<String>,
+}
+
+impl HasSchema for AdClickEvent {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("ad_click", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// A user clicked on a link. Corresponds to the
+/// `iglu:com.snowplowanalytics.snowplow/link_click/jsonschema/1-0-1` schema.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkClickEvent {
+    /// The URL the link points to
+    pub target_url: String,
+
+    /// The `id` attribute of the link element
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub element_id: Option<String>,
+
+    /// The classes of the link element
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub element_classes: Option<Vec<String>>,
+
+    /// The `target` attribute of the link element
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub element_target: Option<String>,
+
+    /// The text content of the link element
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub element_content: Option<String>,
+}
+
+impl HasSchema for LinkClickEvent {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("link_click", SchemaVersion::new(1, 0, 1))
+    }
+}
+
+/// A user progressed to a step of the checkout flow. Corresponds to the
+/// `iglu:com.snowplowanalytics.snowplow.ecommerce/checkout_step/jsonschema/1-0-0`
+/// schema. Attach a [`CartEntity`][crate::entities::CartEntity] as context to
+/// describe the cart being checked out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CheckoutStepEvent {
+    /// The index of this step in the checkout flow, starting at 1
+    pub step: u32,
+
+    /// The postcode that the order will be shipped to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shipping_postcode: Option<String>,
+
+    /// The postcode of the billing address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub billing_postcode: Option<String>,
+
+    /// The full address that the order will be shipped to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shipping_full_address: Option<String>,
+
+    /// The full billing address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub billing_full_address: Option<String>,
+
+    /// The company that will deliver the order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivery_provider: Option<String>,
+
+    /// The delivery option chosen, like `"express"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivery_method: Option<String>,
+
+    /// The coupon code applied to the order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coupon_code: Option<String>,
+
+    /// The type of account used for the checkout, like `"guest"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_type: Option<String>,
+
+    /// The payment method chosen, like `"credit card"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment_method: Option<String>,
+
+    /// The invoice or receipt number
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proof_of_payment: Option<String>,
+
+    /// Whether the user opted in to marketing communications
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marketing_opt_in: Option<bool>,
+}
+
+impl HasSchema for CheckoutStepEvent {
+    fn schema(&self) -> Schema {
+        Schema::new(
+            "com.snowplowanalytics.snowplow.ecommerce",
+            "checkout_step",
+            SchemaVersion::new(1, 0, 0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn cart_event_schemas() {
+        assert_eq!(
+            AddToCartEvent::default().schema().to_string(),
+            "iglu:com.snowplowanalytics.snowplow/add_to_cart/jsonschema/1-0-0"
+        );
+        assert_eq!(
+            RemoveFromCartEvent::default().schema().to_string(),
+            "iglu:com.snowplowanalytics.snowplow/remove_from_cart/jsonschema/1-0-0"
+        );
+    }
+
+    #[test]
+    fn add_to_cart_serializes_numbers() {
+        let event = AddToCartEvent {
+            sku: "sku-1".to_owned(),
+            unit_price: Decimal::new(1999, 2),
+            quantity: 3,
+            ..AddToCartEvent::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({"sku": "sku-1", "unitPrice": 19.99, "quantity": 3})
+        );
+    }
+
+    #[test]
+    fn remove_from_cart_serializes_all_fields() {
+        let event = RemoveFromCartEvent {
+            sku: "sku-1".to_owned(),
+            name: Some("Hat".to_owned()),
+            category: Some("Clothing".to_owned()),
+            unit_price: Decimal::new(5, 0),
+            quantity: 1,
+            currency: Some("EUR".to_owned()),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({
+                "sku": "sku-1",
+                "name": "Hat",
+                "category": "Clothing",
+                "unitPrice": 5.0,
+                "quantity": 1,
+                "currency": "EUR",
+            })
+        );
+    }
+
+    #[test]
+    fn social_interaction_event() {
+        let event = SocialInteractionEvent {
+            action: "like".to_owned(),
+            network: "facebook".to_owned(),
+            target: Some("post-1".to_owned()),
+        };
+
+        assert_eq!(
+            event.schema().to_string(),
+            "iglu:com.snowplowanalytics.snowplow/social_interaction/jsonschema/1-0-0"
+        );
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({"action": "like", "network": "facebook", "target": "post-1"})
+        );
+    }
+
+    #[test]
+    fn ad_event_schemas() {
+        assert_eq!(
+            AdImpressionEvent::default().schema().to_string(),
+            "iglu:com.snowplowanalytics.snowplow/ad_impression/jsonschema/1-0-0"
+        );
+        assert_eq!(
+            AdClickEvent::default().schema().to_string(),
+            "iglu:com.snowplowanalytics.snowplow/ad_click/jsonschema/1-0-0"
+        );
+    }
+
+    #[test]
+    fn cost_model_serialization() {
+        let codes = [
+            (CostModel::PerAcquisition, "cpa"),
+            (CostModel::PerClick, "cpc"),
+            (CostModel::PerMille, "cpm"),
+        ];
+
+        for (model, code) in codes {
+            assert_eq!(serde_json::to_value(model).unwrap(), json!(code));
+        }
+    }
+
+    #[test]
+    fn ad_click_serializes_cost() {
+        let event = AdClickEvent {
+            target_url: "https://example.com".to_owned(),
+            cost_model: Some(CostModel::PerClick),
+            cost: Some(Decimal::new(25, 2)),
+            ..AdClickEvent::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({"targetUrl": "https://example.com", "costModel": "cpc", "cost": 0.25})
+        );
+    }
+
+    #[test]
+    fn empty_ad_impression_serializes_empty() {
+        assert_eq!(
+            serde_json::to_value(AdImpressionEvent::default()).unwrap(),
+            json!({})
+        );
+    }
+
+    #[test]
+    fn link_click_event() {
+        let event = LinkClickEvent {
+            target_url: "https://example.com".to_owned(),
+            element_classes: Some(vec!["nav".to_owned()]),
+            ..LinkClickEvent::default()
+        };
+
+        assert_eq!(
+            event.schema().to_string(),
+            "iglu:com.snowplowanalytics.snowplow/link_click/jsonschema/1-0-1"
+        );
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({"targetUrl": "https://example.com", "elementClasses": ["nav"]})
+        );
+    }
+
+    #[test]
+    fn checkout_step_event() {
+        let event = CheckoutStepEvent {
+            step: 2,
+            delivery_method: Some("express".to_owned()),
+            marketing_opt_in: Some(false),
+            ..CheckoutStepEvent::default()
+        };
+
+        assert_eq!(
+            event.schema().to_string(),
+            "iglu:com.snowplowanalytics.snowplow.ecommerce/checkout_step/jsonschema/1-0-0"
+        );
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({"step": 2, "delivery_method": "express", "marketing_opt_in": false})
+        );
+    }
+}
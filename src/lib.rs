@@ -19,30 +19,93 @@
 //!
 //! ## Example usage
 //!
-//! ```
-//! use snowplow_tracker::{Snowplow, SelfDescribingJson, SelfDescribingEvent};
-//! use serde_json::json;
+//! ```no_run
+//! use snowplow_tracker::{events::LinkClickEvent, Snowplow, TrackedEvent};
 //!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! // Initialize a tracker instance given a namespace, application ID, and Snowplow collector URL
-//! let tracker = Snowplow::create_tracker("ns", "app_id", "https://...");
-//!
-//! // Tracking a self-describing event with a context entity
-//! tracker.track(
-//!     SelfDescribingEvent {
-//!         schema: "iglu:com.snowplowanalytics.snowplow/link_click/jsonschema/1-0-1".to_string(),
-//!         data: json!({"targetUrl": "http://a-target-url.com"})
-//!     },
-//!     Some(vec![
-//!         SelfDescribingJson::new("iglu:org.schema/WebPage/jsonschema/1-0-0", json!({"keywords": ["tester"]}))
-//!     ])
-//! );
+//! let mut snowplow = Snowplow::new();
+//! let tracker = snowplow.create_tracker(
+//!     "ns",
+//!     "app_id",
+//!     "https://collector.example.com/com.snowplowanalytics.snowplow/tp2",
+//! )?;
+//!
+//! // Tracking a link click, one of the standard self-describing events
+//! tracker
+//!     .track(TrackedEvent::new(LinkClickEvent {
+//!         target_url: "http://a-target-url.com".to_owned(),
+//!         ..LinkClickEvent::default()
+//!     }))
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! For more control over the configuration, build a [`Tracker`] directly
+//! from an [`Emitter`][emitter::Emitter] and a [`TrackerConfig`]:
+//!
+//! ```no_run
+//! use snowplow_tracker::{emitter::Emitter, Tracker, TrackerConfig};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let emitter = Emitter::builder(
+//!     "https://collector.example.com/com.snowplowanalytics.snowplow/tp2".parse()?,
+//! )
+//! .build()?;
+//!
+//! let config = TrackerConfig::builder()
+//!     .namespace("ns")
+//!     .app_id("app_id")
+//!     .build();
+//!
+//! let tracker = Tracker::new(emitter, config)?;
+//! # Ok(())
+//! # }
 //! ```
+//!
+//! ## Features
+//!
+//! - `compression`: Advertise `Accept-Encoding` to the collector and
+//!   transparently decompress gzip or brotli responses. This is only needed
+//!   if a proxy between you and the collector compresses responses.
+//! - `uuid-v7`: Allow the tracker to generate time-ordered UUIDv7 event IDs.
+//! - `uds`: Add the `UnixSocketEmitter`, for sending events to a collector
+//!   listening on a Unix domain socket. Only available on unix.
+//! - `default-tls` (enabled by default): Use the platform's native TLS
+//!   library to connect to `https://` collectors.
+//! - `rustls`: Make emitters use rustls instead of the platform's native TLS
+//!   library. This is needed for a TLS 1.3 minimum version. Disable the
+//!   default features along with it to leave out the native TLS library.
+//! - `metrics`: Report delivery statistics through the [`metrics`](https://docs.rs/metrics) facade:
+//!   the `snowplow.events.sent`, `snowplow.events.failed` and
+//!   `snowplow.events.dropped` counters, and the `snowplow.flush.duration`
+//!   histogram, which times each batch sent to the sink. Dropped events are
+//!   duplicates suppressed by the dedup window, and events that couldn't be
+//!   serialized when `drop_unserializable_events` is enabled; they're never
+//!   counted as sent or failed.
 #![deny(missing_docs)]
 
 pub mod emitter;
+pub mod entities;
+pub mod events;
 pub mod payload;
+pub mod registry;
+pub mod sink;
 pub mod tracker;
 pub mod util;
 
-pub use payload::{HasSchema, Platform, Schema, SchemaVersion};
-pub use tracker::{TrackError, TrackedEvent, Tracker, TrackerConfig};
+#[cfg(test)]
+mod testing;
+
+pub use rust_decimal::Decimal;
+
+pub use payload::{HasSchema, Platform, Schema, SchemaError, SchemaVersion, SelfDescribingJson};
+pub use registry::Snowplow;
+pub use sink::EventSink;
+pub use tracker::{
+    ClockSkew, ConfigError, ConnectErrorKind, DropReason, EventIdVersion, RateThreshold,
+    Strictness, TrackBatchSummary, TrackError, TrackManyError, TrackManySummary, Tracked,
+    TrackedEvent, Tracker, TrackerConfig, TrackerConfigBuilder,
+};
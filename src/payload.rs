@@ -19,13 +19,16 @@ Generally you should focus on the types and traits that are part of the
 are only for very custom or very advanced use cases.
 */
 
+use std::borrow::Cow;
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::str::FromStr;
 use std::time::SystemTime;
 
 use serde::ser::SerializeStruct as _;
 use serde::{Serialize, Serializer};
+use thiserror::Error;
 
 use crate::util::JsonString;
 use crate::util::Stringify;
@@ -44,20 +47,37 @@ impl<T: ToString> Serialize for StringySerde<T> {
     }
 }
 
-/// The event type we're sending. Currently we only support "self-describing"
-/// events.
-#[derive(Default, Serialize, Clone, Copy, Debug)]
+/// The event type we're sending. Events are "self-describing" by default, but
+/// [`Custom`][EventType::Custom] allows sending other event type codes; set it
+/// with [`TrackedEvent::with_event_type`][crate::TrackedEvent::with_event_type].
+#[derive(Default, Serialize, Clone, Debug, PartialEq, Eq)]
 pub enum EventType {
     /// An unstructured event, described by a schema.
     #[default]
     #[serde(rename(serialize = "ue"))]
     SelfDescribingEvent,
+
+    /// Any other event type, serialized as the given code. This is useful for
+    /// collectors that recognize event types beyond the standard ones.
+    ///
+    /// ```
+    /// use snowplow_tracker::payload::EventType;
+    ///
+    /// let event_type = EventType::Custom("xe".into());
+    /// assert_eq!(serde_json::to_string(&event_type).unwrap(), r#""xe""#);
+    ///
+    /// // The code can also be chosen at runtime
+    /// let code = String::from("xe");
+    /// assert_eq!(EventType::Custom(code.into()), event_type);
+    /// ```
+    #[serde(untagged)]
+    Custom(Cow<'static, str>),
 }
 
 /// The platform this tracker is being used on. This is generally fixed at
 /// compile time, but this library is broadly cross-platform, so it still needs
 /// to be provided during [`Tracker`][crate::Tracker] configuration.
-#[derive(Debug, Default, Serialize, Clone, Copy)]
+#[derive(Debug, Default, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Platform {
     /// Websites
     #[serde(rename = "web")]
@@ -93,6 +113,31 @@ pub enum Platform {
     Thing,
 }
 
+/// An error parsing a [`Platform`] from a string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Unrecognized Snowplow platform code")]
+pub struct ParsePlatformError;
+
+impl FromStr for Platform {
+    type Err = ParsePlatformError;
+
+    /// Parse a platform from its code in the Snowplow protocol, like `"web"`
+    /// or `"srv"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "web" => Ok(Self::Web),
+            "mob" => Ok(Self::Mobile),
+            "pc" => Ok(Self::Desktop),
+            "srv" => Ok(Self::ServerSide),
+            "app" => Ok(Self::App),
+            "tv" => Ok(Self::Tv),
+            "cnsl" => Ok(Self::GameConsole),
+            "iot" => Ok(Self::Thing),
+            _ => Err(ParsePlatformError),
+        }
+    }
+}
+
 /// A snowplow timestamp. Serializes as the number of seconds since the unix
 /// epoch.
 ///
@@ -120,6 +165,12 @@ impl From<SystemTime> for SnowplowTimestamp {
     }
 }
 
+impl From<SnowplowTimestamp> for SystemTime {
+    fn from(time: SnowplowTimestamp) -> Self {
+        time.timestamp
+    }
+}
+
 impl Serialize for SnowplowTimestamp {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -155,6 +206,11 @@ pub struct SnowplowEvent<'a, Payload: HasSchema> {
     #[serde(rename = "ue_pr")]
     pub payload: JsonString<PayloadWrapper<Payload>>,
 
+    /// The context entities attached to this event, if any
+    #[serde(rename = "co")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contexts: Option<JsonString<Envelope<Contexts>>>,
+
     // ------ APPLICATION PARAMETERS ------
     /// The platform that this tracker is being used on
     #[serde(rename = "p")]
@@ -162,7 +218,7 @@ pub struct SnowplowEvent<'a, Payload: HasSchema> {
 
     /// An identifier describing this app
     #[serde(rename = "aid")]
-    pub app_id: &'a str,
+    pub app_id: Cow<'a, str>,
 
     /// The name of the tracker. This should generally always be the name &
     /// version of this Rust crate
@@ -193,7 +249,59 @@ pub struct SnowplowEvent<'a, Payload: HasSchema> {
     pub sent_timestamp: SnowplowTimestamp,
 }
 
-/// An Iglu Schema version. Renders as `{major}-{minor}-{patch}`
+/// The outermost type that is actually sent to snowplow as a JSON payload.
+/// Includes an outermost schema and a Vec of [`SnowplowEvent`]. This is the
+/// batch that is handed to an [`EventSink`][crate::sink::EventSink]; it
+/// serializes to the same JSON regardless of how it's eventually delivered.
+// TODO: It will be exceedingly common to only need to send a single event;
+// create an optimized version of this type to handle that use case.
+pub type EventContainer<'a, Payload> = Envelope<Vec<SnowplowEvent<'a, Payload>>>;
+
+impl<'a, Payload: HasSchema> EventContainer<'a, Payload> {
+    /// Create a new event container. This will collect all of the given events
+    /// into a [`Vec`].
+    pub fn new(events: impl IntoIterator<Item = SnowplowEvent<'a, Payload>>) -> Self {
+        Envelope(events.into_iter().collect())
+    }
+}
+
+impl<'a, Payload: HasSchema + Serialize> EventContainer<'a, Payload> {
+    /// Serialize this container to a JSON string, optionally pretty-printed
+    /// for readability. This is intended for logging and debugging; the
+    /// [`Emitter`][crate::emitter::Emitter] always sends compact JSON,
+    /// regardless of how this is used.
+    pub fn to_json(&self, pretty: bool) -> Result<String, serde_json::Error> {
+        if pretty {
+            serde_json::to_string_pretty(self)
+        } else {
+            serde_json::to_string(self)
+        }
+    }
+}
+
+impl<'a, Payload: HasSchema> HasSchema for Vec<SnowplowEvent<'a, Payload>> {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("payload_data", SchemaVersion::new(1, 0, 4))
+    }
+}
+
+/// An error building an Iglu [`Schema`] or [`SchemaVersion`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum SchemaError {
+    /// The model (major) version of a schema was 0. Iglu schema versions
+    /// start at `1-0-0`.
+    #[error("Schema model version must be at least 1")]
+    ZeroModel,
+
+    /// A schema URI didn't have the form
+    /// `iglu:{vendor}/{name}/jsonschema/{version}`.
+    #[error("Schema URI is not of the form iglu:vendor/name/jsonschema/version")]
+    InvalidUri,
+}
+
+/// An Iglu Schema version. Renders as `{major}-{minor}-{patch}`. In the
+/// SchemaVer terminology used by Iglu, these are the "model", "revision" and
+/// "addition" components.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(missing_docs)]
 pub struct SchemaVersion {
@@ -212,6 +320,37 @@ impl SchemaVersion {
             patch,
         }
     }
+
+    /// Create a new Snowplow schema version, checking that it's valid
+    /// according to Iglu. In particular, the model version must be at least
+    /// 1, since Iglu schema versions start at `1-0-0`.
+    pub fn try_new(model: u32, revision: u32, addition: u32) -> Result<Self, SchemaError> {
+        match model {
+            0 => Err(SchemaError::ZeroModel),
+            model => Ok(Self::new(model, revision, addition)),
+        }
+    }
+
+    /// The model component of this version, which changes when the schema
+    /// can't be used with any historical data.
+    #[inline]
+    pub fn model(&self) -> u32 {
+        self.major
+    }
+
+    /// The revision component of this version, which changes when the schema
+    /// may be incompatible with some historical data.
+    #[inline]
+    pub fn revision(&self) -> u32 {
+        self.minor
+    }
+
+    /// The addition component of this version, which changes when the schema
+    /// is compatible with all historical data.
+    #[inline]
+    pub fn addition(&self) -> u32 {
+        self.patch
+    }
 }
 
 impl Display for SchemaVersion {
@@ -227,13 +366,13 @@ impl Display for SchemaVersion {
 }
 
 /// An Iglu Schema. Renders as `iglu:{vendor}/{name}/jsonschema/{version}`
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Schema {
     /// Typically a reverse domain name, like "com.agilebits.desktop"
-    pub vendor: &'static str,
+    pub vendor: Cow<'static, str>,
 
     /// The name of this specific schema
-    pub name: &'static str,
+    pub name: Cow<'static, str>,
 
     /// The version of this specific schema
     pub version: SchemaVersion,
@@ -250,8 +389,8 @@ impl Schema {
     #[must_use]
     pub fn new(vendor: &'static str, name: &'static str, version: SchemaVersion) -> Self {
         Self {
-            vendor,
-            name,
+            vendor: Cow::Borrowed(vendor),
+            name: Cow::Borrowed(name),
             version,
         }
     }
@@ -272,12 +411,55 @@ impl Display for Schema {
             vendor,
             name,
             version,
-        } = *self;
+        } = self;
 
         write!(f, "iglu:{vendor}/{name}/jsonschema/{version}")
     }
 }
 
+impl FromStr for Schema {
+    type Err = SchemaError;
+
+    /// Parse a schema from its URI, like
+    /// `"iglu:com.acme/checkout/jsonschema/1-0-0"`. This is useful for
+    /// schemas that aren't known at compile time. The version is checked as
+    /// in [`SchemaVersion::try_new`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let path = s.strip_prefix("iglu:").ok_or(SchemaError::InvalidUri)?;
+
+        let mut parts = path.split('/');
+        let (Some(vendor), Some(name), Some("jsonschema"), Some(version), None) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) else {
+            return Err(SchemaError::InvalidUri);
+        };
+
+        if vendor.is_empty() || name.is_empty() {
+            return Err(SchemaError::InvalidUri);
+        }
+
+        let mut numbers = version.split('-').map(|part| part.parse::<u32>().ok());
+        let (Some(Some(model)), Some(Some(revision)), Some(Some(addition)), None) = (
+            numbers.next(),
+            numbers.next(),
+            numbers.next(),
+            numbers.next(),
+        ) else {
+            return Err(SchemaError::InvalidUri);
+        };
+
+        Ok(Self {
+            vendor: Cow::Owned(vendor.to_owned()),
+            name: Cow::Owned(name.to_owned()),
+            version: SchemaVersion::try_new(model, revision, addition)?,
+        })
+    }
+}
+
 /// Catch-all type for the snowplow data envelope, which combines a snowplow
 /// schema ID with some kind of payload. The payload includes the schema via
 /// the [`HasSchema`] trait. The [`Envelope`] will serialize as an object
@@ -367,3 +549,260 @@ impl<Payload: HasSchema> PayloadWrapper<Payload> {
         Envelope(UnstructWrapper(Envelope(payload)))
     }
 }
+
+/// A self-describing JSON object with a dynamic payload. This is primarily
+/// used for context entities, where a single event can carry several entities
+/// with different schemas. Serializes as `{"schema": "SCHEMA", "data": data}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfDescribingJson {
+    /// The schema describing `data`
+    pub schema: Schema,
+
+    /// The entity data, which should conform to the `schema`
+    pub data: serde_json::Value,
+}
+
+impl SelfDescribingJson {
+    /// Create a new self-describing JSON object out of a schema and some data.
+    pub fn new(schema: Schema, data: serde_json::Value) -> Self {
+        Self { schema, data }
+    }
+
+    /// Create a new self-describing JSON object out of a typed entity, using
+    /// its [`HasSchema`] implementation for the schema. Fails if the entity
+    /// can't be converted to JSON.
+    pub fn from_entity<T: HasSchema + Serialize>(entity: &T) -> Result<Self, serde_json::Error> {
+        Ok(Self::new(entity.schema(), serde_json::to_value(entity)?))
+    }
+}
+
+impl Serialize for SelfDescribingJson {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_struct("SelfDescribingJson", 2)?;
+        map.serialize_field("schema", &Stringify(&self.schema))?;
+        map.serialize_field("data", &self.data)?;
+        map.end()
+    }
+}
+
+/// The list of context entities attached to an event. Mostly exists to supply
+/// the `"iglu:com.snowplowanalytics.snowplow/contexts/jsonschema/1-0-1"` schema
+/// via [`HasSchema`], so that it can be sent in an [`Envelope`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Contexts {
+    /// The context entities
+    pub entities: Vec<SelfDescribingJson>,
+
+    /// The version of the `contexts` schema to describe the entities with.
+    /// This is 1-0-1 unless overridden with
+    /// [`TrackerConfig::contexts_schema_version`][crate::TrackerConfig::contexts_schema_version].
+    #[serde(skip)]
+    pub version: SchemaVersion,
+}
+
+impl Contexts {
+    /// Create a new list of context entities, described by version 1-0-1 of
+    /// the `contexts` schema.
+    pub fn new(entities: Vec<SelfDescribingJson>) -> Self {
+        Self {
+            entities,
+            version: SchemaVersion::new(1, 0, 1),
+        }
+    }
+}
+
+impl Default for Contexts {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl HasSchema for Contexts {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("contexts", self.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Page {
+        url: &'static str,
+    }
+
+    impl HasSchema for Page {
+        fn schema(&self) -> Schema {
+            Schema::new("com.example", "page", SchemaVersion::new(1, 0, 0))
+        }
+    }
+
+    fn event(payload: Page) -> SnowplowEvent<'static, Page> {
+        SnowplowEvent {
+            event_type: EventType::SelfDescribingEvent,
+            payload: JsonString(PayloadWrapper::new(payload)),
+            contexts: None,
+            platform: Platform::Web,
+            app_id: Cow::Borrowed("app"),
+            tracker_id: "tracker",
+            namespace: "ns",
+            event_id: None,
+            created_timestamp: SystemTime::UNIX_EPOCH.into(),
+            sent_timestamp: SystemTime::UNIX_EPOCH.into(),
+        }
+    }
+
+    #[test]
+    fn schema_version_try_new() {
+        assert_eq!(SchemaVersion::try_new(0, 0, 0), Err(SchemaError::ZeroModel));
+
+        let version = SchemaVersion::try_new(2, 1, 3).unwrap();
+        assert_eq!(version.to_string(), "2-1-3");
+        assert_eq!(
+            (version.model(), version.revision(), version.addition()),
+            (2, 1, 3)
+        );
+    }
+
+    #[test]
+    fn schema_display() {
+        let schema = Schema::new("com.example", "page", SchemaVersion::new(1, 0, 2));
+        assert_eq!(schema.to_string(), "iglu:com.example/page/jsonschema/1-0-2");
+    }
+
+    #[test]
+    fn schema_from_str() {
+        let schema: Schema = "iglu:com.acme/checkout/jsonschema/1-2-3".parse().unwrap();
+        assert_eq!(
+            schema,
+            Schema::new("com.acme", "checkout", SchemaVersion::new(1, 2, 3))
+        );
+    }
+
+    #[test]
+    fn schema_from_str_rejects_invalid_uris() {
+        let invalid = [
+            "com.acme/checkout/jsonschema/1-0-0",
+            "iglu:com.acme/checkout/1-0-0",
+            "iglu:com.acme/checkout/jsonschema/1-0",
+            "iglu:com.acme/checkout/jsonschema/1-0-x",
+            "iglu:/checkout/jsonschema/1-0-0",
+            "iglu:com.acme/checkout/jsonschema/1-0-0/extra",
+        ];
+
+        for uri in invalid {
+            assert_eq!(uri.parse::<Schema>(), Err(SchemaError::InvalidUri), "{uri}");
+        }
+
+        assert_eq!(
+            "iglu:com.acme/checkout/jsonschema/0-1-0".parse::<Schema>(),
+            Err(SchemaError::ZeroModel)
+        );
+    }
+
+    #[test]
+    fn platform_from_str_round_trips() {
+        let platforms = [
+            Platform::Web,
+            Platform::Mobile,
+            Platform::Desktop,
+            Platform::ServerSide,
+            Platform::App,
+            Platform::Tv,
+            Platform::GameConsole,
+            Platform::Thing,
+        ];
+
+        for platform in platforms {
+            let code = serde_json::to_value(platform).unwrap();
+            assert_eq!(code.as_str().unwrap().parse(), Ok(platform));
+        }
+
+        assert_eq!("desktop".parse::<Platform>(), Err(ParsePlatformError));
+    }
+
+    #[test]
+    fn timestamp_serializes_millis_as_string() {
+        let timestamp =
+            SnowplowTimestamp::from(SystemTime::UNIX_EPOCH + Duration::from_millis(1234));
+        assert_eq!(serde_json::to_value(timestamp).unwrap(), json!("1234"));
+    }
+
+    #[test]
+    fn event_serialization() {
+        let value = serde_json::to_value(event(Page { url: "/home" })).unwrap();
+
+        assert_eq!(value["e"], "ue");
+        assert_eq!(value["p"], "web");
+        assert_eq!(value["aid"], "app");
+        assert_eq!(value["dtm"], "0");
+        assert!(value.get("co").is_none());
+        assert!(value.get("eid").is_none());
+
+        let ue_pr: serde_json::Value =
+            serde_json::from_str(value["ue_pr"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            ue_pr,
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow/unstruct_event/jsonschema/1-0-0",
+                "data": {
+                    "schema": "iglu:com.example/page/jsonschema/1-0-0",
+                    "data": {"url": "/home"},
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn pretty_json_matches_compact() {
+        let container = EventContainer::new([event(Page { url: "/a" }), event(Page { url: "/b" })]);
+
+        let compact = container.to_json(false).unwrap();
+        let pretty = container.to_json(true).unwrap();
+        assert!(pretty.contains('\n'));
+        assert!(!compact.contains('\n'));
+
+        let compact: serde_json::Value = serde_json::from_str(&compact).unwrap();
+        let pretty: serde_json::Value = serde_json::from_str(&pretty).unwrap();
+        assert_eq!(compact, pretty);
+        assert_eq!(
+            compact["schema"],
+            "iglu:com.snowplowanalytics.snowplow/payload_data/jsonschema/1-0-4"
+        );
+    }
+
+    #[test]
+    fn contexts_schema_version() {
+        let entity = SelfDescribingJson::new(
+            Schema::new("com.example", "entity", SchemaVersion::new(1, 0, 0)),
+            json!({"a": 1}),
+        );
+
+        let contexts = Contexts::new(vec![entity]);
+        assert_eq!(
+            serde_json::to_value(Envelope(contexts.clone())).unwrap(),
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow/contexts/jsonschema/1-0-1",
+                "data": [{"schema": "iglu:com.example/entity/jsonschema/1-0-0", "data": {"a": 1}}],
+            })
+        );
+
+        let contexts = Contexts {
+            version: SchemaVersion::new(1, 0, 0),
+            ..contexts
+        };
+        assert_eq!(
+            serde_json::to_value(Envelope(contexts)).unwrap()["schema"],
+            "iglu:com.snowplowanalytics.snowplow/contexts/jsonschema/1-0-0"
+        );
+    }
+}
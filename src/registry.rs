@@ -0,0 +1,278 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+The [`Snowplow`] registry, for applications that use several logical
+trackers (for instance, with different app IDs or collectors) and want to
+look them up by namespace.
+*/
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use reqwest::Url;
+use serde::Serialize;
+
+use crate::emitter::{Emitter, EmitterBuilder};
+use crate::payload::{HasSchema, Platform};
+use crate::tracker::{ConfigError, Strictness, TrackError, TrackedEvent, Tracker, TrackerConfig};
+
+/// A collection of [`Tracker`]s, each identified by its namespace.
+#[derive(Default)]
+pub struct Snowplow {
+    trackers: HashMap<String, Tracker>,
+}
+
+impl Snowplow {
+    /// Create a new, empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new tracker with the given namespace, app ID and collector
+    /// URL, and add it to this registry. The tracker uses the default
+    /// [`Platform`] and an [`Emitter`] with the default settings of
+    /// [`Emitter::builder`]. Use
+    /// [`create_tracker_with_emitter`][Snowplow::create_tracker_with_emitter]
+    /// to change the emitter settings, or [`register`][Snowplow::register]
+    /// for anything more custom.
+    ///
+    /// Fails if the configuration is invalid (for instance, if the namespace
+    /// contains whitespace), or if a tracker with this namespace is already
+    /// registered.
+    pub fn create_tracker(
+        &mut self,
+        namespace: &str,
+        app_id: impl Into<String>,
+        collector_url: &str,
+    ) -> Result<&Tracker, ConfigError> {
+        let collector_url = collector_url
+            .parse()
+            .map_err(ConfigError::InvalidCollectorUrl)?;
+
+        self.create_tracker_with_emitter(namespace, app_id, Emitter::builder(collector_url))
+    }
+
+    /// Like [`create_tracker`][Snowplow::create_tracker], but the tracker's
+    /// [`Emitter`] is built from the given [`EmitterBuilder`]. This is how to
+    /// change the emitter settings of a registry tracker, like keeping a
+    /// plain `http` collector URL in release builds with
+    /// [`upgrade_to_https(false)`][EmitterBuilder::upgrade_to_https].
+    pub fn create_tracker_with_emitter(
+        &mut self,
+        namespace: &str,
+        app_id: impl Into<String>,
+        emitter: EmitterBuilder,
+    ) -> Result<&Tracker, ConfigError> {
+        let emitter = emitter.build().map_err(ConfigError::HttpClient)?;
+
+        let config = TrackerConfig {
+            namespace: namespace.to_owned(),
+            platform: Platform::default(),
+            app_id: app_id.into(),
+            ..TrackerConfig::default()
+        };
+
+        self.register(Tracker::new(emitter, config)?)
+    }
+
+    /// Create a tracker that accepts events like any other, but drops them
+    /// instead of sending them anywhere, so every call to `track` succeeds
+    /// without any network activity. This is useful when analytics are
+    /// disabled, like with a feature flag, so that the rest of the code can
+    /// keep tracking events unconditionally. The tracker is an ordinary
+    /// [`Tracker`] with [`TrackerConfig::disabled`] set, so it can be used
+    /// wherever an enabled one is, including in
+    /// [`register`][Snowplow::register], but it isn't added to any registry.
+    /// To disable a tracker with a specific namespace or collector, set
+    /// [`TrackerConfig::disabled`] directly.
+    pub fn create_noop_tracker() -> Tracker {
+        let config = TrackerConfig {
+            empty_identifier_check: Strictness::Ignore,
+            disabled: true,
+            ..TrackerConfig::default()
+        };
+
+        // The emitter is never used, since a disabled tracker drops every
+        // event before it's sent
+        let url = Url::parse("http://localhost/").expect("the placeholder URL is valid");
+        let emitter = Emitter::new(url, reqwest::Client::new());
+
+        Tracker::new(emitter, config).expect("the default tracker configuration is valid")
+    }
+
+    /// Add an existing tracker to this registry, under its namespace. Fails
+    /// if a tracker with this namespace is already registered.
+    pub fn register(&mut self, tracker: Tracker) -> Result<&Tracker, ConfigError> {
+        match self.trackers.entry(tracker.namespace().to_owned()) {
+            Entry::Occupied(entry) => Err(ConfigError::DuplicateNamespace(entry.key().clone())),
+            Entry::Vacant(entry) => Ok(entry.insert(tracker)),
+        }
+    }
+
+    /// Get the tracker with the given namespace, if there is one.
+    pub fn tracker(&self, namespace: &str) -> Option<&Tracker> {
+        self.trackers.get(namespace)
+    }
+
+    /// Track an event with every registered tracker. Every tracker is
+    /// attempted even if some of them fail; in that case, the first error
+    /// is returned.
+    pub async fn track_all<Payload: HasSchema + Serialize + Send + Sync + Clone>(
+        &self,
+        event: TrackedEvent<Payload>,
+    ) -> Result<(), TrackError> {
+        let mut result = Ok(());
+
+        for tracker in self.trackers.values() {
+            let outcome = tracker.track(event.clone()).await;
+            result = result.and(outcome.map(|_| ()));
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MockCollector, MockResponse, TestEvent};
+    use crate::tracker::{DropReason, Tracked};
+
+    fn tracker(namespace: &str, collector: &MockCollector) -> Tracker {
+        let emitter = Emitter::builder(collector.url())
+            .upgrade_to_https(false)
+            .build()
+            .unwrap();
+        let config = TrackerConfig::builder()
+            .namespace(namespace)
+            .app_id("app_id")
+            .build();
+
+        Tracker::new(emitter, config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn trackers_are_retrieved_by_namespace() {
+        let first = MockCollector::start(MockResponse::ok()).await;
+        let second = MockCollector::start(MockResponse::ok()).await;
+
+        let mut snowplow = Snowplow::new();
+        snowplow.register(tracker("first", &first)).unwrap();
+        snowplow.register(tracker("second", &second)).unwrap();
+
+        assert!(snowplow.tracker("third").is_none());
+
+        let tracker = snowplow.tracker("first").unwrap();
+        assert_eq!(tracker.namespace(), "first");
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .unwrap();
+
+        let tracker = snowplow.tracker("second").unwrap();
+        assert_eq!(tracker.namespace(), "second");
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 2 }))
+            .await
+            .unwrap();
+
+        assert_eq!(first.requests().len(), 1);
+        assert_eq!(first.requests()[0].json()["data"][0]["tna"], "first");
+        assert_eq!(second.requests().len(), 1);
+        assert_eq!(second.requests()[0].json()["data"][0]["tna"], "second");
+    }
+
+    #[tokio::test]
+    async fn duplicate_namespaces_are_rejected() {
+        let collector = MockCollector::start(MockResponse::ok()).await;
+
+        let mut snowplow = Snowplow::new();
+        snowplow.register(tracker("ns", &collector)).unwrap();
+        assert!(matches!(
+            snowplow.register(tracker("ns", &collector)),
+            Err(ConfigError::DuplicateNamespace(namespace)) if namespace == "ns"
+        ));
+    }
+
+    #[tokio::test]
+    async fn track_all_sends_to_every_tracker() {
+        let first = MockCollector::start(MockResponse::ok()).await;
+        let second = MockCollector::start(MockResponse::ok()).await;
+
+        let mut snowplow = Snowplow::new();
+        snowplow.register(tracker("first", &first)).unwrap();
+        snowplow.register(tracker("second", &second)).unwrap();
+
+        snowplow
+            .track_all(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .unwrap();
+
+        assert_eq!(first.requests().len(), 1);
+        assert_eq!(second.requests().len(), 1);
+    }
+
+    #[test]
+    fn create_tracker_rejects_invalid_config() {
+        let mut snowplow = Snowplow::new();
+        assert!(matches!(
+            snowplow.create_tracker("ns", "app", "not a url"),
+            Err(ConfigError::InvalidCollectorUrl(_))
+        ));
+        assert!(matches!(
+            snowplow.create_tracker("bad ns", "app", "https://collector.example.com"),
+            Err(ConfigError::InvalidNamespace(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn created_tracker_fails_on_redirect() {
+        let collector = MockCollector::start(
+            MockResponse::status(302).header("location", "http://127.0.0.1:1/elsewhere"),
+        )
+        .await;
+
+        let emitter = Emitter::builder(collector.url()).upgrade_to_https(false);
+        let mut snowplow = Snowplow::new();
+        let tracker = snowplow
+            .create_tracker_with_emitter("ns", "app_id", emitter)
+            .unwrap();
+
+        assert!(matches!(
+            tracker.track(TrackedEvent::new(TestEvent { id: 1 })).await,
+            Err(TrackError::Redirected { .. })
+        ));
+        assert_eq!(collector.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn noop_tracker_accepts_events() {
+        let mut tracker = Snowplow::create_noop_tracker();
+        tracker.add_context_provider(|| panic!("a disabled tracker doesn't build contexts"));
+
+        let tracked = tracker
+            .track(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .unwrap();
+        assert_eq!(tracked, Tracked::Dropped(DropReason::Disabled));
+
+        let summary = tracker
+            .track_batch((0..10).map(|id| TrackedEvent::new(TestEvent { id })))
+            .await
+            .unwrap();
+        assert_eq!(summary.disabled, 10);
+        assert_eq!(summary.sent, 0);
+
+        let mut snowplow = Snowplow::new();
+        snowplow.register(tracker).unwrap();
+    }
+}
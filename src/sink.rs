@@ -0,0 +1,268 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+The [`EventSink`] trait, which is the extension point for delivering events
+somewhere other than a Snowplow Collector. The [`Tracker`][crate::Tracker]
+builds complete events and hands them to its sink in batches; the
+[`Emitter`][crate::emitter::Emitter] is the sink that sends them over HTTP.
+*/
+
+use std::future::Future;
+
+use serde::Serialize;
+
+use crate::payload::{EventContainer, HasSchema};
+use crate::tracker::TrackError;
+
+/**
+A destination for batches of fully-formed Snowplow events. Implement this to
+route events through your own transport, like a message queue, and then
+create a [`Tracker`][crate::Tracker] with [`Tracker::new`][crate::Tracker::new]
+using your sink in place of an [`Emitter`][crate::emitter::Emitter].
+
+The batch is an [`EventContainer`], which serializes to exactly the
+`payload_data` envelope that a Collector would receive, so a sink generally
+only needs to serialize it and send the result.
+
+```
+use std::sync::Mutex;
+
+use serde::Serialize;
+use snowplow_tracker::payload::EventContainer;
+use snowplow_tracker::events::LinkClickEvent;
+use snowplow_tracker::{EventSink, HasSchema, Platform, TrackError, TrackedEvent, Tracker, TrackerConfig};
+
+/// A sink that just collects every batch it receives
+#[derive(Default)]
+struct VecSink {
+    batches: Mutex<Vec<serde_json::Value>>,
+}
+
+impl EventSink for VecSink {
+    async fn emit<Payload: HasSchema + Serialize + Send + Sync>(
+        &self,
+        batch: EventContainer<'_, Payload>,
+    ) -> Result<(), TrackError> {
+        let batch = serde_json::to_value(&batch).map_err(|err| TrackError::Sink(err.into()))?;
+        self.batches.lock().unwrap().push(batch);
+        Ok(())
+    }
+}
+
+# #[tokio::main]
+# async fn main() -> Result<(), Box<dyn std::error::Error>> {
+let sink = VecSink::default();
+let config = TrackerConfig::builder()
+    .namespace("ns")
+    .platform(Platform::App)
+    .app_id("app_id")
+    .build();
+let tracker = Tracker::new(&sink, config)?;
+
+tracker
+    .track(TrackedEvent::new(LinkClickEvent {
+        target_url: "http://a-target-url.com".to_owned(),
+        ..LinkClickEvent::default()
+    }))
+    .await?;
+
+let batches = sink.batches.lock().unwrap();
+assert_eq!(batches.len(), 1);
+assert_eq!(batches[0]["data"][0]["aid"], "app_id");
+# Ok(())
+# }
+```
+*/
+pub trait EventSink {
+    /// Deliver a batch of events. The batch should be considered delivered
+    /// once the returned future resolves successfully.
+    ///
+    /// The future must be `Send`, so that a tracker with any sink can be used
+    /// from a spawned task. That's automatically the case for an `async fn`
+    /// that doesn't hold anything like a `std::sync::MutexGuard` across an
+    /// `.await`.
+    fn emit<Payload: HasSchema + Serialize + Send + Sync>(
+        &self,
+        batch: EventContainer<'_, Payload>,
+    ) -> impl Future<Output = Result<(), TrackError>> + Send;
+}
+
+impl<S: EventSink> EventSink for &S {
+    fn emit<Payload: HasSchema + Serialize + Send + Sync>(
+        &self,
+        batch: EventContainer<'_, Payload>,
+    ) -> impl Future<Output = Result<(), TrackError>> + Send {
+        S::emit(*self, batch)
+    }
+}
+
+impl<S: EventSink> EventSink for Box<S> {
+    fn emit<Payload: HasSchema + Serialize + Send + Sync>(
+        &self,
+        batch: EventContainer<'_, Payload>,
+    ) -> impl Future<Output = Result<(), TrackError>> + Send {
+        S::emit(self.as_ref(), batch)
+    }
+}
+
+/// An [`EventSink`] that discards every batch and always succeeds. This is
+/// useful when analytics are turned off, so that code can keep tracking
+/// events unconditionally with a generic `Tracker<NoopSink>`. To turn off a
+/// [`Tracker`][crate::Tracker] that sends to an [`Emitter`][crate::emitter::Emitter],
+/// set [`TrackerConfig::disabled`][crate::TrackerConfig::disabled] instead.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopSink;
+
+impl EventSink for NoopSink {
+    async fn emit<Payload: HasSchema + Serialize + Send + Sync>(
+        &self,
+        _batch: EventContainer<'_, Payload>,
+    ) -> Result<(), TrackError> {
+        Ok(())
+    }
+}
+
+/**
+A serialization format for event batches. The [`Emitter`][crate::emitter::Emitter]
+always sends JSON, since that's what a Collector expects, but a custom
+[`EventSink`] can be made generic over a `Serializer` so that it can deliver
+batches in another format, like CBOR or MessagePack. [`JsonSerializer`] is the
+default.
+
+```
+use std::sync::Mutex;
+
+use serde::Serialize;
+use snowplow_tracker::events::LinkClickEvent;
+use snowplow_tracker::payload::EventContainer;
+use snowplow_tracker::sink::Serializer;
+use snowplow_tracker::{EventSink, HasSchema, Platform, TrackError, TrackedEvent, Tracker, TrackerConfig};
+
+/// Serializes batches as CBOR
+struct CborSerializer;
+
+impl Serializer for CborSerializer {
+    type Error = ciborium::ser::Error<std::io::Error>;
+
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, Self::Error> {
+        let mut buffer = Vec::new();
+        ciborium::into_writer(value, &mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+/// A sink that stores each serialized batch
+struct BytesSink<S> {
+    serializer: S,
+    batches: Mutex<Vec<Vec<u8>>>,
+}
+
+impl<S: Serializer + Sync> EventSink for BytesSink<S> {
+    async fn emit<Payload: HasSchema + Serialize + Send + Sync>(
+        &self,
+        batch: EventContainer<'_, Payload>,
+    ) -> Result<(), TrackError> {
+        let bytes = self
+            .serializer
+            .serialize(&batch)
+            .map_err(|err| TrackError::Sink(err.into()))?;
+
+        self.batches.lock().unwrap().push(bytes);
+        Ok(())
+    }
+}
+
+# #[tokio::main]
+# async fn main() -> Result<(), Box<dyn std::error::Error>> {
+let sink = BytesSink {
+    serializer: CborSerializer,
+    batches: Mutex::new(Vec::new()),
+};
+let config = TrackerConfig::builder()
+    .namespace("ns")
+    .platform(Platform::App)
+    .app_id("app_id")
+    .build();
+let tracker = Tracker::new(&sink, config)?;
+
+tracker
+    .track(TrackedEvent::new(LinkClickEvent {
+        target_url: "http://a-target-url.com".to_owned(),
+        ..LinkClickEvent::default()
+    }))
+    .await?;
+
+let batches = sink.batches.lock().unwrap();
+let batch: serde_json::Value = ciborium::from_reader(batches[0].as_slice())?;
+assert_eq!(
+    batch["schema"],
+    "iglu:com.snowplowanalytics.snowplow/payload_data/jsonschema/1-0-4"
+);
+assert_eq!(batch["data"][0]["aid"], "app_id");
+# Ok(())
+# }
+```
+*/
+pub trait Serializer {
+    /// The error returned when a value can't be serialized
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Serialize a value, typically an
+    /// [`EventContainer`][crate::payload::EventContainer], into bytes.
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, Self::Error>;
+}
+
+/// The default [`Serializer`], which produces compact JSON. This is the same
+/// format used by the [`Emitter`][crate::emitter::Emitter].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonSerializer;
+
+impl Serializer for JsonSerializer {
+    type Error = serde_json::Error;
+
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, Self::Error> {
+        serde_json::to_vec(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, RecordingSink, TestEvent};
+    use crate::tracker::{TrackedEvent, Tracker};
+
+    #[tokio::test]
+    async fn references_and_boxes_forward_to_the_sink() {
+        let sink = RecordingSink::default();
+
+        Tracker::new(&sink, testing::config())
+            .unwrap()
+            .track(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .unwrap();
+
+        Tracker::new(Box::new(&sink), testing::config())
+            .unwrap()
+            .track(TrackedEvent::new(TestEvent { id: 2 }))
+            .await
+            .unwrap();
+
+        assert_eq!(sink.batches().len(), 2);
+    }
+
+    #[test]
+    fn json_serializer_produces_compact_json() {
+        let value = serde_json::json!({"a": [1, 2], "b": "c"});
+        let bytes = JsonSerializer.serialize(&value).unwrap();
+        assert_eq!(bytes, serde_json::to_vec(&value).unwrap());
+    }
+}
@@ -0,0 +1,406 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+Helpers shared by the unit tests: a [`MockCollector`] that records the HTTP
+requests it receives, and a [`RecordingSink`] that records the batches a
+tracker emits without any networking.
+*/
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tokio::io::{
+    AsyncBufReadExt as _, AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _, BufReader,
+};
+use tokio::net::TcpListener;
+use tokio::sync::Notify;
+use url::Url;
+
+use crate::payload::{EventContainer, HasSchema, Schema, SchemaVersion};
+use crate::sink::EventSink;
+use crate::tracker::{TrackError, TrackerConfig};
+
+/// A request received by a [`MockCollector`].
+#[derive(Debug, Clone)]
+pub(crate) struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercased
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).expect("request body is JSON")
+    }
+}
+
+/// The response a [`MockCollector`] sends to a request.
+#[derive(Debug, Clone)]
+pub(crate) struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+    /// If set, the response is only sent once this is notified
+    pub release: Option<Arc<Notify>>,
+}
+
+impl MockResponse {
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+            release: None,
+        }
+    }
+
+    pub fn ok() -> Self {
+        Self::status(200)
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    pub fn wait_for(mut self, release: Arc<Notify>) -> Self {
+        self.release = Some(release);
+        self
+    }
+}
+
+type Handler = Arc<dyn Fn(&RecordedRequest) -> MockResponse + Send + Sync>;
+
+/// A minimal HTTP/1.1 server on a local port, standing in for a Snowplow
+/// collector. Every request is recorded, and answered by a handler.
+pub(crate) struct MockCollector {
+    url: Url,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl MockCollector {
+    /// Start a collector that answers every request with `response`.
+    pub async fn start(response: MockResponse) -> Self {
+        Self::with_handler(move |_| response.clone()).await
+    }
+
+    /// Start a collector that answers each request with the response
+    /// returned by `handler`.
+    pub async fn with_handler(
+        handler: impl Fn(&RecordedRequest) -> MockResponse + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("binding to a local port");
+
+        let address = listener.local_addr().expect("listener has an address");
+        let url = format!("http://{address}/com.snowplowanalytics.snowplow/tp2")
+            .parse()
+            .expect("mock collector URL is valid");
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Handler = Arc::new(handler);
+
+        tokio::spawn({
+            let requests = Arc::clone(&requests);
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream, Arc::clone(&handler), Arc::clone(&requests)));
+                }
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// Start a collector listening on a Unix domain socket at `path`, that
+    /// answers every request with `response`. Its [`url`][Self::url] only
+    /// gives the request path.
+    #[cfg(all(unix, feature = "uds"))]
+    pub fn start_unix(path: &std::path::Path, response: MockResponse) -> Self {
+        let listener = tokio::net::UnixListener::bind(path).expect("binding to a Unix socket");
+        let url = "http://localhost/com.snowplowanalytics.snowplow/tp2"
+            .parse()
+            .expect("mock collector URL is valid");
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Handler = Arc::new(move |_: &RecordedRequest| response.clone());
+
+        tokio::spawn({
+            let requests = Arc::clone(&requests);
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream, Arc::clone(&handler), Arc::clone(&requests)));
+                }
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// The URL of the tracking endpoint of this collector.
+    pub fn url(&self) -> Url {
+        self.url.clone()
+    }
+
+    /// Every request received so far, in the order they were received.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn serve(
+    stream: impl AsyncRead + AsyncWrite + Unpin,
+    handler: Handler,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+) -> std::io::Result<()> {
+    let mut stream = BufReader::new(stream);
+
+    // Serve requests until the client closes the connection
+    while let Some(request) = read_request(&mut stream).await? {
+        requests.lock().unwrap().push(request.clone());
+        let response = handler(&request);
+        if let Some(release) = &response.release {
+            release.notified().await;
+        }
+
+        let mut head = format!(
+            "HTTP/1.1 {} Mock\r\ncontent-length: {}\r\n",
+            response.status,
+            response.body.len()
+        );
+        for (name, value) in &response.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str("\r\n");
+
+        let stream = stream.get_mut();
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(&response.body).await?;
+        stream.flush().await?;
+    }
+
+    Ok(())
+}
+
+async fn read_request(
+    stream: &mut BufReader<impl AsyncRead + Unpin>,
+) -> std::io::Result<Option<RecordedRequest>> {
+    let mut request_line = String::new();
+    if stream.read_line(&mut request_line).await? == 0 {
+        return Ok(None);
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let path = parts.next().unwrap_or_default().to_owned();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        stream.read_line(&mut line).await?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_owned());
+        }
+    }
+
+    let length = headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body).await?;
+
+    Ok(Some(RecordedRequest {
+        method,
+        path,
+        headers,
+        body,
+    }))
+}
+
+/// An [`EventSink`] that records each batch as JSON, and can be made to fail.
+#[derive(Default)]
+pub(crate) struct RecordingSink {
+    batches: Mutex<Vec<serde_json::Value>>,
+    failing: AtomicBool,
+}
+
+impl RecordingSink {
+    /// Every batch emitted so far.
+    pub fn batches(&self) -> Vec<serde_json::Value> {
+        self.batches.lock().unwrap().clone()
+    }
+
+    /// Every event emitted so far, across all batches.
+    pub fn events(&self) -> Vec<serde_json::Value> {
+        self.batches()
+            .iter()
+            .flat_map(|batch| batch["data"].as_array().unwrap().clone())
+            .collect()
+    }
+
+    /// Make every subsequent emit fail, or succeed again.
+    pub fn set_failing(&self, failing: bool) {
+        self.failing.store(failing, Ordering::SeqCst);
+    }
+}
+
+impl EventSink for RecordingSink {
+    async fn emit<Payload: HasSchema + Serialize + Send + Sync>(
+        &self,
+        batch: EventContainer<'_, Payload>,
+    ) -> Result<(), TrackError> {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err(TrackError::Sink("the sink is failing".into()));
+        }
+
+        let batch = serde_json::to_value(&batch).map_err(|err| TrackError::Sink(err.into()))?;
+        self.batches.lock().unwrap().push(batch);
+        Ok(())
+    }
+}
+
+/// A path in the temporary directory that's unique to this process and
+/// `name`. Any file already at the path is removed.
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("snowplow-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+/// A simple event payload for tests.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct TestEvent {
+    pub id: u32,
+}
+
+impl HasSchema for TestEvent {
+    fn schema(&self) -> Schema {
+        Schema::new("com.example", "test_event", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// A valid configuration for a test tracker.
+pub(crate) fn config() -> TrackerConfig {
+    TrackerConfig::builder()
+        .namespace("ns")
+        .app_id("app_id")
+        .build()
+}
+
+/// The decoded `ue_pr` of an emitted event: the innermost `{schema, data}`.
+pub(crate) fn unstruct_data(event: &serde_json::Value) -> serde_json::Value {
+    let ue_pr: serde_json::Value =
+        serde_json::from_str(event["ue_pr"].as_str().expect("event has ue_pr")).unwrap();
+    ue_pr["data"].clone()
+}
+
+/// The context entities attached to an emitted event, decoded from `co`.
+pub(crate) fn contexts_of(event: &serde_json::Value) -> Vec<serde_json::Value> {
+    match event.get("co") {
+        None => Vec::new(),
+        Some(co) => {
+            let co: serde_json::Value = serde_json::from_str(co.as_str().unwrap()).unwrap();
+            co["data"].as_array().unwrap().clone()
+        }
+    }
+}
+
+/// The warnings logged while a [`capture_warnings`] guard is alive.
+#[derive(Clone, Default)]
+pub(crate) struct Warnings(Arc<Mutex<Vec<Warning>>>);
+
+#[derive(Default)]
+struct Warning {
+    message: String,
+    /// The message and every other field, as they would be logged
+    output: String,
+}
+
+impl tracing::field::Visit for Warning {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        }
+        self.output += &format!(" {}={value:?}", field.name());
+    }
+}
+
+impl Warnings {
+    pub fn messages(&self) -> Vec<String> {
+        let warnings = self.0.lock().unwrap();
+        warnings
+            .iter()
+            .map(|warning| warning.message.clone())
+            .collect()
+    }
+
+    /// Everything that was logged, with one line per warning.
+    pub fn output(&self) -> String {
+        let warnings = self.0.lock().unwrap();
+        let lines: Vec<_> = warnings
+            .iter()
+            .map(|warning| warning.output.as_str())
+            .collect();
+        lines.join("\n")
+    }
+}
+
+/// Capture the warnings logged on the current thread until the returned
+/// guard is dropped. Tests using this need a current-thread runtime, which is
+/// the default for `#[tokio::test]`.
+pub(crate) fn capture_warnings() -> (tracing::subscriber::DefaultGuard, Warnings) {
+    let warnings = Warnings::default();
+    let guard = tracing::subscriber::set_default(WarningSubscriber(warnings.clone()));
+    (guard, warnings)
+}
+
+struct WarningSubscriber(Warnings);
+
+impl tracing::Subscriber for WarningSubscriber {
+    fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        *metadata.level() == tracing::Level::WARN
+    }
+
+    fn new_span(&self, _span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        tracing::span::Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        let mut warning = Warning::default();
+        event.record(&mut warning);
+        (self.0).0.lock().unwrap().push(warning);
+    }
+
+    fn enter(&self, _span: &tracing::span::Id) {}
+
+    fn exit(&self, _span: &tracing::span::Id) {}
+}
@@ -15,15 +15,28 @@ The types in this module are your main entry point to this library.
 */
 
 use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
 use url::Url;
 use uuid::Uuid;
 
 use crate::{
     emitter::Emitter,
-    payload::{EventType, HasSchema, PayloadWrapper, Platform, SnowplowEvent, SnowplowTimestamp},
-    util::JsonString,
+    entities::{InstanceContext, RuntimeContext},
+    events::{AddToCartEvent, RemoveFromCartEvent},
+    payload::{
+        Contexts, Envelope, EventContainer, EventType, HasSchema, PayloadWrapper, Platform, Schema,
+        SchemaError, SchemaVersion, SelfDescribingJson, SnowplowEvent, SnowplowTimestamp,
+    },
+    sink::EventSink,
+    util::{ByteCounter, Encoded, JsonEncoding, JsonString},
 };
 
 /// An error encountered when submitting an event for tracking. Generally
@@ -32,9 +45,392 @@ use crate::{
 pub enum TrackError {
     /// There was an HTTP error sending the event– the response was malformed,
     /// or there was a TCP error. This variant does *not* include HTTP error
-    /// codes.
+    /// codes, or failures to connect, which are reported as
+    /// [`Connect`][TrackError::Connect].
     #[error("Unexpected error during HTTP request (not an error code)")]
-    HttpConnection(#[from] reqwest::Error),
+    HttpConnection(#[source] reqwest::Error),
+
+    /// The connection to the collector couldn't be established, so the
+    /// events weren't sent. The `kind` distinguishes the common causes, so
+    /// that, for instance, a persistent DNS failure can be told apart from a
+    /// collector that is briefly down.
+    #[error("Failed to connect to the collector: {kind}")]
+    Connect {
+        /// What went wrong while connecting
+        kind: ConnectErrorKind,
+        /// The underlying error, like the `reqwest::Error` from an
+        /// [`Emitter`], or the `io::Error` from a `UnixSocketEmitter`
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// The collector responded with a redirect, which wasn't followed, so the
+    /// events weren't delivered. This usually means that the collector URL is
+    /// wrong; see [`EmitterBuilder::redirect`][crate::emitter::EmitterBuilder::redirect].
+    #[error("Collector responded with a redirect ({status}) to {location:?}")]
+    Redirected {
+        /// The redirect status code
+        status: reqwest::StatusCode,
+        /// The target of the redirect, if the collector supplied one
+        location: Option<String>,
+    },
+
+    /// A custom [`EventSink`] failed to emit the events.
+    #[error("Error emitting events to the event sink")]
+    Sink(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    /// The tracker is configured for [`Platform::Mobile`] and to reject events
+    /// without a mobile context, and an event didn't have one. See
+    /// [`TrackerConfig::mobile_context_check`].
+    #[error("Event tracked on the mobile platform without a mobile context")]
+    MissingMobileContext,
+
+    /// The schema URI of a raw event couldn't be parsed. See
+    /// [`Tracker::track_raw`].
+    #[error("Invalid schema URI for raw event")]
+    InvalidSchema(#[source] SchemaError),
+
+    /// The data of a raw event wasn't a JSON object. See
+    /// [`Tracker::track_raw`].
+    #[error("Raw event data must be a JSON object")]
+    RawDataNotObject,
+
+    /// A serialized event was larger than the configured limit. See
+    /// [`TrackerConfig::max_event_bytes`].
+    #[error("Serialized event is {size} bytes, which exceeds the limit of {limit} bytes")]
+    EventTooLarge {
+        /// The size of the serialized event, in bytes
+        size: usize,
+        /// The configured limit
+        limit: usize,
+    },
+}
+
+impl From<reqwest::Error> for TrackError {
+    fn from(err: reqwest::Error) -> Self {
+        if !err.is_connect() {
+            return Self::HttpConnection(err);
+        }
+
+        Self::Connect {
+            kind: ConnectErrorKind::classify(&err),
+            source: err.into(),
+        }
+    }
+}
+
+impl From<std::convert::Infallible> for TrackError {
+    fn from(never: std::convert::Infallible) -> Self {
+        match never {}
+    }
+}
+
+/// The cause of a [`TrackError::Connect`] error. The cause is determined on
+/// a best-effort basis from the underlying error, so unrecognized failures
+/// are reported as [`Other`][ConnectErrorKind::Other].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectErrorKind {
+    /// The collector's host name couldn't be resolved. This is only
+    /// recognized for emitters created with
+    /// [`Emitter::builder`][crate::emitter::Emitter::builder]; with the
+    /// custom client of [`Emitter::new`], it's reported as
+    /// [`Other`][ConnectErrorKind::Other].
+    Dns,
+
+    /// The collector's host refused the connection, which usually means that
+    /// nothing is listening on the port
+    Refused,
+
+    /// The TLS handshake with the collector failed, for instance because its
+    /// certificate isn't trusted
+    Tls,
+
+    /// Any other connection failure, like a connection timeout
+    Other,
+}
+
+impl ConnectErrorKind {
+    pub(crate) fn classify(err: &(dyn std::error::Error + 'static)) -> Self {
+        // DNS failures are recognized by the error type of the emitter's
+        // resolver, and TLS failures by the type of the error from the TLS
+        // library.
+        let mut source: Option<&(dyn std::error::Error + 'static)> = Some(err);
+
+        while let Some(error) = source {
+            if let Some(io_error) = error.downcast_ref::<std::io::Error>() {
+                if io_error.kind() == std::io::ErrorKind::ConnectionRefused {
+                    return Self::Refused;
+                }
+            }
+
+            if error.is::<crate::emitter::DnsError>() {
+                return Self::Dns;
+            }
+
+            if is_tls_error(error) {
+                return Self::Tls;
+            }
+
+            source = match error.downcast_ref::<std::io::Error>() {
+                // The `source` of an `io::Error` skips the error it wraps,
+                // which is where the TLS libraries put their errors.
+                Some(io_error) => match io_error.get_ref() {
+                    Some(inner) => Some(inner),
+                    None => error.source(),
+                },
+                None => error.source(),
+            };
+        }
+
+        Self::Other
+    }
+}
+
+#[cfg_attr(
+    not(any(feature = "default-tls", feature = "rustls")),
+    allow(unused_variables)
+)]
+fn is_tls_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    #[cfg(feature = "rustls")]
+    if error.is::<rustls::Error>() {
+        return true;
+    }
+
+    #[cfg(feature = "default-tls")]
+    if error.is::<native_tls::Error>() {
+        return true;
+    }
+
+    false
+}
+
+impl std::fmt::Display for ConnectErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Dns => "DNS resolution failed",
+            Self::Refused => "connection refused",
+            Self::Tls => "TLS handshake failed",
+            Self::Other => "connection failed",
+        })
+    }
+}
+
+/// What happened to an event tracked by [`Tracker::track`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tracked {
+    /// The event was sent to the tracker's sink
+    Sent,
+
+    /// The event was dropped by the tracker without being sent
+    Dropped(DropReason),
+}
+
+/// Why the tracker dropped an event instead of sending it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DropReason {
+    /// The event is a duplicate of one sent within the
+    /// [`dedup_window`][TrackerConfig::dedup_window]
+    Duplicate,
+
+    /// The event couldn't be serialized, and
+    /// [`drop_unserializable_events`][TrackerConfig::drop_unserializable_events]
+    /// is enabled
+    Unserializable,
+
+    /// The tracker is [`disabled`][TrackerConfig::disabled]
+    Disabled,
+}
+
+/// What happened to the events of a batch tracked by
+/// [`Tracker::track_batch`]: how many were sent, and how many were dropped
+/// and why.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrackBatchSummary {
+    /// The number of events sent to the tracker's sink
+    pub sent: usize,
+
+    /// The number of events dropped as [duplicates][DropReason::Duplicate]
+    pub duplicates: usize,
+
+    /// The number of events dropped as
+    /// [unserializable][DropReason::Unserializable]
+    pub unserializable: usize,
+
+    /// The number of events dropped because the tracker is
+    /// [disabled][DropReason::Disabled]
+    pub disabled: usize,
+}
+
+impl TrackBatchSummary {
+    /// The number of events dropped for any reason.
+    pub fn dropped(&self) -> usize {
+        self.duplicates + self.unserializable + self.disabled
+    }
+
+    /// The outcome of a batch with a single event.
+    fn single(&self) -> Tracked {
+        if self.disabled > 0 {
+            Tracked::Dropped(DropReason::Disabled)
+        } else if self.duplicates > 0 {
+            Tracked::Dropped(DropReason::Duplicate)
+        } else if self.unserializable > 0 {
+            Tracked::Dropped(DropReason::Unserializable)
+        } else {
+            Tracked::Sent
+        }
+    }
+}
+
+/// How much of the input [`Tracker::track_many`] got through: the number of
+/// events tracked, and the number of batches they were tracked in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrackManySummary {
+    /// The number of events tracked, including the ones that were dropped
+    pub events: usize,
+
+    /// The number of events tracked but dropped, as described by
+    /// [`TrackBatchSummary::dropped`]
+    pub dropped: usize,
+
+    /// The number of batches tracked
+    pub batches: usize,
+}
+
+/// A batch tracked by [`Tracker::track_many`] failed. The batches before it
+/// were already tracked, and are described by `delivered`; the events of the
+/// failed batch and any after it weren't.
+#[derive(Debug, Error)]
+#[error("Failed to track batch {} after tracking {} events", delivered.batches + 1, delivered.events)]
+pub struct TrackManyError {
+    /// The events and batches tracked before the failure
+    pub delivered: TrackManySummary,
+
+    /// The error from the failed batch
+    #[source]
+    pub error: TrackError,
+}
+
+/// An error in a [`TrackerConfig`], detected when a [`Tracker`] is created.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    /// The configured default currency isn't a 3-letter ISO 4217 code, like
+    /// `"USD"` or `"EUR"`.
+    #[error("Default currency {0:?} is not a 3-letter ISO 4217 currency code")]
+    InvalidCurrency(String),
+
+    /// The namespace contains whitespace or control characters. See
+    /// [`TrackerConfig::namespace`].
+    #[error("Namespace {0:?} contains whitespace or control characters")]
+    InvalidNamespace(String),
+
+    /// The collector URL couldn't be parsed.
+    #[error("Invalid collector URL")]
+    InvalidCollectorUrl(#[source] url::ParseError),
+
+    /// The HTTP client for the collector couldn't be created. See
+    /// [`EmitterBuilder::build`][crate::emitter::EmitterBuilder::build].
+    #[error("Failed to create the HTTP client")]
+    HttpClient(#[source] reqwest::Error),
+
+    /// A tracker with this namespace is already registered with a
+    /// [`Snowplow`][crate::Snowplow] registry.
+    #[error("A tracker with namespace {0:?} is already registered")]
+    DuplicateNamespace(String),
+
+    /// The app ID or namespace is empty, and the tracker is configured to
+    /// reject that. The field is `"app_id"` or `"namespace"`. See
+    /// [`TrackerConfig::empty_identifier_check`].
+    #[error("The {0} of the tracker is empty")]
+    EmptyIdentifier(&'static str),
+
+    /// A required environment variable wasn't set. See
+    /// [`TrackerConfig::from_env`].
+    #[error("Missing environment variable {0}")]
+    MissingEnvVar(&'static str),
+
+    /// An environment variable had a value that couldn't be used. See
+    /// [`TrackerConfig::from_env`].
+    #[error("Invalid value {value:?} for environment variable {name}")]
+    InvalidEnvVar {
+        /// The name of the environment variable
+        name: &'static str,
+        /// The offending value
+        value: String,
+    },
+}
+
+/// How strictly the tracker enforces a particular check.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Don't perform the check
+    #[default]
+    Ignore,
+
+    /// Log a warning when the check fails, but otherwise carry on
+    Warn,
+
+    /// Fail with an error when the check fails
+    Deny,
+}
+
+/// The UUID version used when the tracker generates an event ID.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EventIdVersion {
+    /// Random UUIDs
+    #[default]
+    V4,
+
+    /// Time-ordered UUIDs, which give better locality when events are stored
+    /// in a database indexed by their ID. Requires the `uuid-v7` feature.
+    #[cfg(feature = "uuid-v7")]
+    V7,
+}
+
+impl EventIdVersion {
+    fn generate(self) -> Uuid {
+        match self {
+            Self::V4 => Uuid::new_v4(),
+            #[cfg(feature = "uuid-v7")]
+            Self::V7 => Uuid::now_v7(),
+        }
+    }
+}
+
+/// A correction applied to the timestamps of every event sent by a tracker,
+/// for sources whose clock is known to be skewed. See
+/// [`TrackerConfig::clock_skew`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockSkew {
+    /// Shift timestamps later by this amount, for a clock that runs behind
+    Forward(Duration),
+
+    /// Shift timestamps earlier by this amount, for a clock that runs ahead
+    Backward(Duration),
+}
+
+impl ClockSkew {
+    fn apply(self, timestamp: SnowplowTimestamp) -> SnowplowTimestamp {
+        let time = SystemTime::from(timestamp);
+        match self {
+            Self::Forward(skew) => time.checked_add(skew),
+            Self::Backward(skew) => time.checked_sub(skew),
+        }
+        .unwrap_or(time)
+        .into()
+    }
+}
+
+/// A rate of events, like "100 events per second". See
+/// [`TrackerConfig::schema_rate_warning`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateThreshold {
+    /// The number of events allowed in each interval
+    pub events: u64,
+
+    /// The length of the interval
+    pub interval: Duration,
 }
 
 /// The tracker ID, corresponding to the `tv` field of a snowplow event.
@@ -49,14 +445,506 @@ const TRACKER_ID: &str = concat!("rust-fork-", env!("CARGO_PKG_VERSION"));
 /// tracker.
 #[derive(Debug, Clone)]
 pub struct TrackerConfig {
-    /// The namespace this tracker will use
-    pub namespace: &'static str,
+    /// The namespace this tracker will use. This is sent with every event, so
+    /// it must not contain whitespace or control characters; it should
+    /// generally be a short URL-safe identifier, like `"web-tracker"`.
+    pub namespace: String,
 
     /// The platform we're operating on. If unsure, App is a good default.
     pub platform: Platform,
 
     /// An identifier for this specific application
     pub app_id: String,
+
+    /// The currency used by ecommerce events that don't specify their own,
+    /// as a 3-letter ISO 4217 code like `"USD"`. A currency set on an
+    /// individual event always takes precedence over this default.
+    ///
+    /// The default is applied to every tracked event, however it's tracked,
+    /// whose payload or context entity has one of the ecommerce schemas with
+    /// a currency: an [`AddToCartEvent`] or [`RemoveFromCartEvent`] without
+    /// a currency, or a [`ProductEntity`][crate::entities::ProductEntity] or
+    /// [`CartEntity`][crate::entities::CartEntity] context with an empty one,
+    /// like the cart attached to a
+    /// [`CheckoutStepEvent`][crate::events::CheckoutStepEvent]. This includes
+    /// such events tracked with [`track_raw`][Tracker::track_raw].
+    pub default_currency: Option<String>,
+
+    /// If set, events whose payload is identical to one delivered within
+    /// this window, or to an earlier event in the same batch, are silently
+    /// dropped. Events are compared by schema and payload data, with object
+    /// keys sorted; their IDs, timestamps and contexts are ignored. This is
+    /// useful for suppressing accidental duplicates, like double clicks.
+    ///
+    /// Events are only remembered once their batch has been delivered, so a
+    /// batch that fails can be retried within the window. This also means
+    /// that identical events tracked concurrently, before either has been
+    /// delivered, are all sent.
+    pub dedup_window: Option<Duration>,
+
+    /// If set, a warning is logged when events with the same schema are
+    /// tracked more often than this, which usually means they're being
+    /// tracked in a loop by mistake. At most one warning is logged per schema
+    /// in each interval.
+    pub schema_rate_warning: Option<RateThreshold>,
+
+    /// How to handle events tracked without a mobile context when the
+    /// `platform` is [`Platform::Mobile`]. Snowplow effectively requires the
+    /// mobile context for mobile events, so a missing one usually indicates a
+    /// misconfiguration.
+    pub mobile_context_check: Strictness,
+
+    /// How to handle an empty `app_id` or `namespace` when the tracker is
+    /// created. Events with an empty `aid` or `tna` are hard to make sense
+    /// of downstream, so this is usually a misconfiguration. Defaults to
+    /// [`Strictness::Warn`].
+    pub empty_identifier_check: Strictness,
+
+    /// A soft limit on the number of contexts attached to a single event.
+    /// Exceeding it logs a warning listing the attached schemas, which helps
+    /// catch bugs like contexts being attached in a loop.
+    pub max_contexts_per_event: Option<usize>,
+
+    /// If enabled, events exceeding `max_contexts_per_event` have their
+    /// excess contexts discarded, keeping the first ones attached.
+    pub truncate_excess_contexts: bool,
+
+    /// Schemas whose contexts are moved to the front of each event's
+    /// contexts, in this order, so that downstream consumers see them in a
+    /// predictable position. Schemas are matched by vendor and name,
+    /// regardless of version. Contexts with unlisted schemas keep their
+    /// relative order after the prioritized ones. This is applied before
+    /// `truncate_excess_contexts`, so prioritized contexts are kept.
+    pub context_priority: Vec<Schema>,
+
+    /// If set, tracking fails with [`TrackError::EventTooLarge`] when any
+    /// single event in a batch is larger than this many bytes once
+    /// serialized. None of the events in the batch are sent. This is useful
+    /// for catching bugs that produce huge payloads.
+    pub max_event_bytes: Option<usize>,
+
+    /// If enabled, each event of a batch is serialized on its own before the
+    /// batch is sent, and events that can't be serialized are dropped with a
+    /// warning, so that the rest of the batch is still delivered. If
+    /// disabled (the default), one event that can't be serialized makes the
+    /// whole batch fail.
+    pub drop_unserializable_events: bool,
+
+    /// Contexts that are automatically attached to every event, depending on
+    /// the configured `platform`. For instance, a configuration shared
+    /// between apps could attach a web page context on [`Platform::Web`] and a
+    /// mobile context on [`Platform::Mobile`]. Only the contexts registered for
+    /// this tracker's platform are used.
+    pub platform_contexts: HashMap<Platform, Vec<SelfDescribingJson>>,
+
+    /// The UUID version to use when the tracker generates an event ID, such
+    /// as in [`Tracker::track_with_id`].
+    pub event_id_version: EventIdVersion,
+
+    /// If set, a [`RuntimeContext`] describing the operating system, CPU
+    /// architecture and tracker version is attached to every event, using
+    /// this schema. Since there's no standard schema for this context, you
+    /// need to supply the one registered in your own Iglu registry.
+    pub runtime_context_schema: Option<Schema>,
+
+    /// If set, an [`InstanceContext`] with the host name and process ID is
+    /// attached to every event, using this schema. As with
+    /// `runtime_context_schema`, you need to supply your own schema.
+    pub instance_context_schema: Option<Schema>,
+
+    /// If enabled (the default), each event's sent timestamp (`stm`) is the
+    /// moment it's tracked. If disabled, it's equal to the event's created
+    /// timestamp (`dtm`) instead, which some forensic use cases require. In
+    /// either case, an explicit [`TrackedEvent::sent_timestamp`] takes
+    /// precedence.
+    pub rewrite_sent_timestamp: bool,
+
+    /// If enabled, the events of each batch are sorted by their creation
+    /// timestamp (`dtm`) before they're sent, which improves downstream
+    /// ordering when replaying events from an unordered source. Disabled by
+    /// default, so events are sent in the order they're tracked.
+    pub sort_by_timestamp: bool,
+
+    /// If enabled, the keys of every JSON object in event payloads and
+    /// context entities are sorted, so that identical events always
+    /// serialize to identical JSON, whatever the iteration order of the maps
+    /// they were built from. This covers [`track_raw`][Tracker::track_raw],
+    /// the [`platform_contexts`][TrackerConfig::platform_contexts] and the
+    /// contexts from context providers. Disabled by default, since it costs a
+    /// conversion to [`serde_json::Value`] for each payload.
+    pub sort_keys: bool,
+
+    /// If enabled, every boolean in event payloads and context entities,
+    /// including nested ones, is sent as the string `"1"` or `"0"`, for
+    /// legacy collectors or enrichments that expect booleans in that form.
+    /// Like [`sort_keys`][TrackerConfig::sort_keys], this covers
+    /// [`track_raw`][Tracker::track_raw], the
+    /// [`platform_contexts`][TrackerConfig::platform_contexts] and the
+    /// contexts from context providers. Disabled by default.
+    pub stringify_bools: bool,
+
+    /// If set, this correction is applied to both the creation (`dtm`) and
+    /// sent (`stm`) timestamps of every event, including timestamps set
+    /// explicitly on a [`TrackedEvent`]. Use this when the clock of the
+    /// source is known to be off, so the events are corrected before they
+    /// reach the pipeline.
+    pub clock_skew: Option<ClockSkew>,
+
+    /// The version of the `contexts` schema that wraps the context entities
+    /// of each event (the `co` field). If omitted, 1-0-1 is used. Only
+    /// override this if your pipeline expects a different version.
+    pub contexts_schema_version: Option<SchemaVersion>,
+
+    /// If enabled, the tracker drops every event as
+    /// [`Disabled`][DropReason::Disabled] as soon as it's tracked, without
+    /// calling the context providers, serializing it or sending it. This is
+    /// useful when analytics are turned off at runtime, like with a feature
+    /// flag, so that the rest of the code can keep tracking events
+    /// unconditionally with the same type of [`Tracker`]. Disabled (so the
+    /// tracker is enabled) by default.
+    pub disabled: bool,
+}
+
+impl TrackerConfig {
+    /// Load a tracker configuration from environment variables:
+    ///
+    /// - `SNOWPLOW_NAMESPACE` (required): the tracker namespace
+    /// - `SNOWPLOW_APP_ID` (required): the app ID
+    /// - `SNOWPLOW_PLATFORM`: the platform code, like `"srv"` or `"mob"`.
+    ///   Defaults to [`Platform::App`].
+    /// - `SNOWPLOW_DEFAULT_CURRENCY`: the default currency for ecommerce
+    ///   events
+    ///
+    /// All other options take their default values. Fails if a required
+    /// variable is missing or if any variable has an invalid value. See also
+    /// [`Tracker::from_env`], which additionally reads the collector URL.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let platform = match optional_env_var("SNOWPLOW_PLATFORM")? {
+            None => Platform::default(),
+            Some(value) => value.parse().map_err(|_| ConfigError::InvalidEnvVar {
+                name: "SNOWPLOW_PLATFORM",
+                value,
+            })?,
+        };
+
+        let namespace = required_env_var("SNOWPLOW_NAMESPACE")?;
+        let app_id = required_env_var("SNOWPLOW_APP_ID")?;
+
+        Ok(Self {
+            namespace,
+            platform,
+            app_id,
+            default_currency: optional_env_var("SNOWPLOW_DEFAULT_CURRENCY")?,
+            ..Self::default()
+        })
+    }
+
+    /// Create a [`TrackerConfigBuilder`], for building a configuration with
+    /// fluent setters instead of a struct literal. Options that aren't set
+    /// take their [default][TrackerConfig::default] values, so code using the
+    /// builder keeps working when new options are added.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use snowplow_tracker::{Platform, TrackerConfig};
+    ///
+    /// let config = TrackerConfig::builder()
+    ///     .namespace("web-tracker")
+    ///     .app_id("my-app")
+    ///     .platform(Platform::Web)
+    ///     .dedup_window(Duration::from_secs(1))
+    ///     .build();
+    ///
+    /// assert_eq!(config.app_id, "my-app");
+    /// assert_eq!(config.dedup_window, Some(Duration::from_secs(1)));
+    /// assert!(config.rewrite_sent_timestamp);
+    /// ```
+    pub fn builder() -> TrackerConfigBuilder {
+        TrackerConfigBuilder::default()
+    }
+
+    fn json_encoding(&self) -> JsonEncoding<'_> {
+        JsonEncoding {
+            sort_keys: self.sort_keys,
+            stringify_bools: self.stringify_bools,
+            default_currency: None,
+        }
+    }
+
+    /// The encoding for a payload or context entity with the given schema,
+    /// which fills in the [`default_currency`][TrackerConfig::default_currency]
+    /// if the schema has a currency.
+    fn json_encoding_for(&self, schema: &Schema) -> JsonEncoding<'_> {
+        let has_currency = match &*schema.vendor {
+            "com.snowplowanalytics.snowplow" => {
+                matches!(&*schema.name, "add_to_cart" | "remove_from_cart")
+            }
+            "com.snowplowanalytics.snowplow.ecommerce" => {
+                matches!(&*schema.name, "product" | "cart")
+            }
+            _ => false,
+        };
+
+        JsonEncoding {
+            default_currency: self.default_currency.as_deref().filter(|_| has_currency),
+            ..self.json_encoding()
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self
+            .namespace
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control())
+        {
+            return Err(ConfigError::InvalidNamespace(self.namespace.clone()));
+        }
+
+        for (field, value) in [("app_id", &self.app_id), ("namespace", &self.namespace)] {
+            if !value.is_empty() {
+                continue;
+            }
+
+            match self.empty_identifier_check {
+                Strictness::Ignore => {}
+                Strictness::Warn => tracing::warn!(field, "Snowplow tracker has an empty {field}"),
+                Strictness::Deny => return Err(ConfigError::EmptyIdentifier(field)),
+            }
+        }
+
+        if let Some(currency) = &self.default_currency {
+            let is_iso_code =
+                currency.len() == 3 && currency.bytes().all(|b| b.is_ascii_uppercase());
+
+            if !is_iso_code {
+                return Err(ConfigError::InvalidCurrency(currency.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The default configuration has an empty namespace and app ID, which should
+/// generally be set, and every other option disabled or set to its most
+/// conservative value.
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self {
+            namespace: String::new(),
+            platform: Platform::default(),
+            app_id: String::new(),
+            default_currency: None,
+            dedup_window: None,
+            schema_rate_warning: None,
+            mobile_context_check: Strictness::Ignore,
+            empty_identifier_check: Strictness::Warn,
+            max_contexts_per_event: None,
+            truncate_excess_contexts: false,
+            context_priority: Vec::new(),
+            max_event_bytes: None,
+            drop_unserializable_events: false,
+            platform_contexts: HashMap::new(),
+            event_id_version: EventIdVersion::V4,
+            runtime_context_schema: None,
+            instance_context_schema: None,
+            rewrite_sent_timestamp: true,
+            sort_by_timestamp: false,
+            sort_keys: false,
+            stringify_bools: false,
+            clock_skew: None,
+            contexts_schema_version: None,
+            disabled: false,
+        }
+    }
+}
+
+/// Builder for a [`TrackerConfig`]. Create one with
+/// [`TrackerConfig::builder`]. Each setter corresponds to the field of the
+/// same name; see [`TrackerConfig`] for what they do. The configuration is
+/// validated when it's used to create a [`Tracker`].
+#[derive(Debug, Clone, Default)]
+pub struct TrackerConfigBuilder {
+    config: TrackerConfig,
+}
+
+impl TrackerConfigBuilder {
+    /// Set the [`namespace`][TrackerConfig::namespace].
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.config.namespace = namespace.into();
+        self
+    }
+
+    /// Set the [`platform`][TrackerConfig::platform].
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.config.platform = platform;
+        self
+    }
+
+    /// Set the [`app_id`][TrackerConfig::app_id].
+    pub fn app_id(mut self, app_id: impl Into<String>) -> Self {
+        self.config.app_id = app_id.into();
+        self
+    }
+
+    /// Set the [`default_currency`][TrackerConfig::default_currency].
+    pub fn default_currency(mut self, currency: impl Into<String>) -> Self {
+        self.config.default_currency = Some(currency.into());
+        self
+    }
+
+    /// Set the [`dedup_window`][TrackerConfig::dedup_window].
+    pub fn dedup_window(mut self, window: Duration) -> Self {
+        self.config.dedup_window = Some(window);
+        self
+    }
+
+    /// Set the [`schema_rate_warning`][TrackerConfig::schema_rate_warning].
+    pub fn schema_rate_warning(mut self, threshold: RateThreshold) -> Self {
+        self.config.schema_rate_warning = Some(threshold);
+        self
+    }
+
+    /// Set the [`mobile_context_check`][TrackerConfig::mobile_context_check].
+    pub fn mobile_context_check(mut self, strictness: Strictness) -> Self {
+        self.config.mobile_context_check = strictness;
+        self
+    }
+
+    /// Set the
+    /// [`empty_identifier_check`][TrackerConfig::empty_identifier_check].
+    pub fn empty_identifier_check(mut self, strictness: Strictness) -> Self {
+        self.config.empty_identifier_check = strictness;
+        self
+    }
+
+    /// Set the
+    /// [`max_contexts_per_event`][TrackerConfig::max_contexts_per_event].
+    pub fn max_contexts_per_event(mut self, limit: usize) -> Self {
+        self.config.max_contexts_per_event = Some(limit);
+        self
+    }
+
+    /// Set
+    /// [`truncate_excess_contexts`][TrackerConfig::truncate_excess_contexts].
+    pub fn truncate_excess_contexts(mut self, enabled: bool) -> Self {
+        self.config.truncate_excess_contexts = enabled;
+        self
+    }
+
+    /// Set the [`context_priority`][TrackerConfig::context_priority].
+    pub fn context_priority(mut self, schemas: impl IntoIterator<Item = Schema>) -> Self {
+        self.config.context_priority = schemas.into_iter().collect();
+        self
+    }
+
+    /// Set the [`max_event_bytes`][TrackerConfig::max_event_bytes].
+    pub fn max_event_bytes(mut self, limit: usize) -> Self {
+        self.config.max_event_bytes = Some(limit);
+        self
+    }
+
+    /// Set
+    /// [`drop_unserializable_events`][TrackerConfig::drop_unserializable_events].
+    pub fn drop_unserializable_events(mut self, enabled: bool) -> Self {
+        self.config.drop_unserializable_events = enabled;
+        self
+    }
+
+    /// Add a context to the [`platform_contexts`][TrackerConfig::platform_contexts]
+    /// of the given platform.
+    pub fn platform_context(mut self, platform: Platform, context: SelfDescribingJson) -> Self {
+        self.config
+            .platform_contexts
+            .entry(platform)
+            .or_default()
+            .push(context);
+        self
+    }
+
+    /// Set the [`event_id_version`][TrackerConfig::event_id_version].
+    pub fn event_id_version(mut self, version: EventIdVersion) -> Self {
+        self.config.event_id_version = version;
+        self
+    }
+
+    /// Set the
+    /// [`runtime_context_schema`][TrackerConfig::runtime_context_schema].
+    pub fn runtime_context_schema(mut self, schema: Schema) -> Self {
+        self.config.runtime_context_schema = Some(schema);
+        self
+    }
+
+    /// Set the
+    /// [`instance_context_schema`][TrackerConfig::instance_context_schema].
+    pub fn instance_context_schema(mut self, schema: Schema) -> Self {
+        self.config.instance_context_schema = Some(schema);
+        self
+    }
+
+    /// Set
+    /// [`rewrite_sent_timestamp`][TrackerConfig::rewrite_sent_timestamp].
+    pub fn rewrite_sent_timestamp(mut self, enabled: bool) -> Self {
+        self.config.rewrite_sent_timestamp = enabled;
+        self
+    }
+
+    /// Set [`sort_by_timestamp`][TrackerConfig::sort_by_timestamp].
+    pub fn sort_by_timestamp(mut self, enabled: bool) -> Self {
+        self.config.sort_by_timestamp = enabled;
+        self
+    }
+
+    /// Set [`sort_keys`][TrackerConfig::sort_keys].
+    pub fn sort_keys(mut self, enabled: bool) -> Self {
+        self.config.sort_keys = enabled;
+        self
+    }
+
+    /// Set [`stringify_bools`][TrackerConfig::stringify_bools].
+    pub fn stringify_bools(mut self, enabled: bool) -> Self {
+        self.config.stringify_bools = enabled;
+        self
+    }
+
+    /// Set the [`clock_skew`][TrackerConfig::clock_skew].
+    pub fn clock_skew(mut self, skew: ClockSkew) -> Self {
+        self.config.clock_skew = Some(skew);
+        self
+    }
+
+    /// Set the
+    /// [`contexts_schema_version`][TrackerConfig::contexts_schema_version].
+    pub fn contexts_schema_version(mut self, version: SchemaVersion) -> Self {
+        self.config.contexts_schema_version = Some(version);
+        self
+    }
+
+    /// Set [`disabled`][TrackerConfig::disabled].
+    pub fn disabled(mut self, disabled: bool) -> Self {
+        self.config.disabled = disabled;
+        self
+    }
+
+    /// Build the [`TrackerConfig`].
+    pub fn build(self) -> TrackerConfig {
+        self.config
+    }
+}
+
+fn optional_env_var(name: &'static str) -> Result<Option<String>, ConfigError> {
+    match std::env::var(name) {
+        Ok(value) => Ok(Some(value)),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(std::env::VarError::NotUnicode(value)) => Err(ConfigError::InvalidEnvVar {
+            name,
+            value: value.to_string_lossy().into_owned(),
+        }),
+    }
+}
+
+fn required_env_var(name: &'static str) -> Result<String, ConfigError> {
+    optional_env_var(name)?.ok_or(ConfigError::MissingEnvVar(name))
 }
 
 /// Snowplow tracker instance used to track events to the Snowplow Collector.
@@ -64,83 +952,657 @@ pub struct TrackerConfig {
 /// The main purpose of the tracker is to build full snowplow event objects
 /// out of the [`TrackedEvent`] objects you pass into it. It takes care of
 /// stuff like event type, timestamps, app & tracker IDs, etc.
-pub struct Tracker {
-    /// Emitter used to send events to the Collector
-    emitter: Emitter,
+///
+/// Events are delivered by an [`EventSink`], which is an [`Emitter`] sending
+/// them to a Collector over HTTP unless you supply your own.
+pub struct Tracker<S = Emitter> {
+    /// Sink used to deliver events, usually to the Collector
+    sink: S,
     /// Additional tracker config
     config: TrackerConfig,
+    /// Filter for duplicate events, if `dedup_window` is configured
+    dedup: Option<DedupFilter>,
+    /// Counts of events per schema, if `schema_rate_warning` is configured
+    rate_monitor: Option<RateMonitor>,
+    /// Functions that supply extra contexts for each tracked event
+    context_providers: Vec<ContextProvider>,
 }
 
+/// A function that supplies a context entity to attach to each tracked
+/// event. See [`Tracker::add_context_provider`].
+type ContextProvider = Box<dyn Fn() -> Option<SelfDescribingJson> + Send + Sync>;
+
 impl Tracker {
-    /// Create a new tracker directly out of its constituent parts
+    /// Create a new tracker directly out of its constituent parts. Returns an
+    /// error if the resulting configuration is invalid.
     ///
     /// Note that a snowplow collector URL usually has
     /// `'/com.snowplowanalytics.snowplow/tp2'` as its path. Unlike most
     /// snowplow trackers, we as you to include the full path, in case you want
     /// to change it for your specific collector configuration.
+    ///
+    /// The `client` is used as-is, like with [`Emitter::new`], so it doesn't
+    /// get the safe defaults of [`Emitter::builder`]; in particular, a
+    /// default `reqwest::Client` follows redirects, which loses the events.
     pub fn build(
-        namespace: &'static str,
+        namespace: &str,
         app_id: String,
         platform: Platform,
         url: Url,
         client: reqwest::Client,
-    ) -> Self {
+    ) -> Result<Self, ConfigError> {
         Self::new(
             Emitter::new(url, client),
             TrackerConfig {
-                namespace,
+                namespace: namespace.to_owned(),
                 platform,
                 app_id,
+                ..TrackerConfig::default()
             },
         )
     }
 
-    /// Create a new tracker
-    pub fn new(emitter: Emitter, config: TrackerConfig) -> Tracker {
-        Tracker { emitter, config }
+    /// Create a new tracker from environment variables. The collector URL is
+    /// read from `SNOWPLOW_COLLECTOR_URL`, which is required, and the rest of
+    /// the configuration is read as described in [`TrackerConfig::from_env`].
+    /// The emitter is built with the defaults of [`Emitter::builder`].
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let collector_url = required_env_var("SNOWPLOW_COLLECTOR_URL")?
+            .parse()
+            .map_err(ConfigError::InvalidCollectorUrl)?;
+
+        let emitter = Emitter::builder(collector_url)
+            .build()
+            .map_err(ConfigError::HttpClient)?;
+
+        Self::new(emitter, TrackerConfig::from_env()?)
+    }
+}
+
+impl<S: EventSink> Tracker<S> {
+    /// Create a new tracker. Returns an error if the `config` is invalid.
+    pub fn new(sink: S, config: TrackerConfig) -> Result<Self, ConfigError> {
+        config.validate()?;
+
+        let mut tracker = Tracker {
+            sink,
+            dedup: config
+                .dedup_window
+                .map(|window| DedupFilter::new(window, config.json_encoding())),
+            rate_monitor: config.schema_rate_warning.map(RateMonitor::new),
+            config,
+            context_providers: Vec::new(),
+        };
+
+        if let Some(schema) = tracker.config.runtime_context_schema.clone() {
+            let context = RuntimeContext::detect().to_context(schema);
+            tracker.add_context_provider(move || Some(context.clone()));
+        }
+
+        if let Some(schema) = tracker.config.instance_context_schema.clone() {
+            let context = InstanceContext::detect().to_context(schema);
+            tracker.add_context_provider(move || Some(context.clone()));
+        }
+
+        Ok(tracker)
+    }
+
+    /// Register a function that supplies a context entity for every event
+    /// tracked with this tracker. The function is called each time an event is
+    /// tracked, so it can capture dynamic values, like the trace ID of the
+    /// request currently being handled. If it returns `None`, no context is
+    /// added for that event.
+    pub fn add_context_provider(
+        &mut self,
+        provider: impl Fn() -> Option<SelfDescribingJson> + Send + Sync + 'static,
+    ) {
+        self.context_providers.push(Box::new(provider));
+    }
+
+    /// The namespace of this tracker, which distinguishes it from other
+    /// trackers in the same application.
+    pub fn namespace(&self) -> &str {
+        &self.config.namespace
+    }
+
+    /// The sink that this tracker sends events to, like its [`Emitter`].
+    pub fn sink(&self) -> &S {
+        &self.sink
     }
 
     /// Tracks a Snowplow event and send it to the Snowplow collector.
-    pub async fn track<Payload: HasSchema + Serialize>(
+    /// Returns whether the event was sent, or dropped by the tracker, like a
+    /// duplicate within the [`dedup_window`][TrackerConfig::dedup_window].
+    pub async fn track<Payload: HasSchema + Serialize + Send + Sync>(
         &self,
         event: TrackedEvent<Payload>,
-    ) -> Result<(), TrackError> {
-        self.track_batch([event]).await
+    ) -> Result<Tracked, TrackError> {
+        self.track_batch([event])
+            .await
+            .map(|summary| summary.single())
     }
 
-    /// Track a batch of events, sending them to the snowplow collector.
-    pub async fn track_batch<Payload: HasSchema + Serialize>(
+    /// Tracks a Snowplow event like [`track`][Tracker::track], and returns its
+    /// event ID on success, so that it can be logged or joined on downstream,
+    /// along with whether it was sent. If the event doesn't have an ID, one
+    /// is generated for it, according to [`TrackerConfig::event_id_version`].
+    pub async fn track_with_id<Payload: HasSchema + Serialize + Send + Sync>(
+        &self,
+        mut event: TrackedEvent<Payload>,
+    ) -> Result<(Uuid, Tracked), TrackError> {
+        let version = self.config.event_id_version;
+        let id = *event.id.get_or_insert_with(|| version.generate());
+        self.track(event).await.map(|tracked| (id, tracked))
+    }
+
+    /// Track a large number of events, like when importing them from a log
+    /// file, by splitting them into batches of `batch_size` events and
+    /// tracking each batch with [`track_batch`][Tracker::track_batch] in
+    /// turn. Events are pulled from the iterator lazily, so only one batch is
+    /// held in memory at a time.
+    ///
+    /// Returns how many events and batches were tracked. Stops at the first
+    /// batch that fails; the error includes the same summary of the batches
+    /// before it, which have already been sent.
+    pub async fn track_many<Payload: HasSchema + Serialize + Send + Sync>(
         &self,
         events: impl IntoIterator<Item = TrackedEvent<Payload>>,
-    ) -> Result<(), TrackError> {
-        let now = SnowplowTimestamp::now();
+        batch_size: NonZeroUsize,
+    ) -> Result<TrackManySummary, TrackManyError> {
+        let mut summary = TrackManySummary::default();
 
-        let events = events.into_iter().map(|event| SnowplowEvent {
-            event_type: EventType::SelfDescribingEvent,
-            payload: JsonString(PayloadWrapper::new(event.payload)),
-            platform: self.config.platform,
-            app_id: &self.config.app_id,
-            tracker_id: TRACKER_ID,
-            namespace: self.config.namespace,
-            event_id: event.id,
-            created_timestamp: event.timestamp.unwrap_or(now),
-            sent_timestamp: now,
-        });
+        let mut events = events.into_iter();
+        loop {
+            let batch: Vec<_> = events.by_ref().take(batch_size.get()).collect();
+            if batch.is_empty() {
+                return Ok(summary);
+            }
 
-        self.emitter
-            .track_events(events)
-            .await
-            .map_err(TrackError::HttpConnection)
+            let count = batch.len();
+            let tracked = match self.track_batch(batch).await {
+                Ok(tracked) => tracked,
+                Err(error) => {
+                    return Err(TrackManyError {
+                        delivered: summary,
+                        error,
+                    })
+                }
+            };
+
+            summary.events += count;
+            summary.dropped += tracked.dropped();
+            summary.batches += 1;
+        }
     }
-}
 
-/// An event to be sent to the tracker. Mostly this is a vehicle for your
-/// Unstructured payload, but also allows you to include your own fields for
-/// the top-level snowplow event
-#[derive(Debug, Clone, Default)]
-pub struct TrackedEvent<T: HasSchema + Serialize> {
-    /// Your specific event payload. The tracker will handle correctly wrapping
-    /// and encoding this according to the Snowplow protocol, so all you need
+    /// Track a batch of events, sending them to the snowplow collector.
+    /// Returns how many of the events were sent, and how many were dropped
+    /// by the tracker.
+    pub async fn track_batch<Payload: HasSchema + Serialize + Send + Sync>(
+        &self,
+        events: impl IntoIterator<Item = TrackedEvent<Payload>>,
+    ) -> Result<TrackBatchSummary, TrackError> {
+        if self.config.disabled {
+            return Ok(TrackBatchSummary {
+                disabled: events.into_iter().count(),
+                ..TrackBatchSummary::default()
+            });
+        }
+
+        let mut duplicates = 0;
+        let mut fingerprints = Vec::new();
+        let events: Vec<_> = events
+            .into_iter()
+            .filter(|event| match &self.dedup {
+                Some(dedup) if dedup.is_duplicate(&event.payload, &mut fingerprints) => {
+                    duplicates += 1;
+                    false
+                }
+                _ => true,
+            })
+            .collect();
+
+        if let Some(monitor) = &self.rate_monitor {
+            monitor.record(events.iter().map(|event| event.payload.schema()));
+        }
+
+        let summary = self.send_batch(events).await?;
+
+        // Only remember events once they've been delivered, so that a retry
+        // of a batch that failed isn't mistaken for a duplicate.
+        if let Some(dedup) = &self.dedup {
+            dedup.record(&fingerprints);
+        }
+
+        Ok(TrackBatchSummary {
+            duplicates,
+            ..summary
+        })
+    }
+
+    async fn send_batch<Payload: HasSchema + Serialize + Send + Sync>(
+        &self,
+        events: Vec<TrackedEvent<Payload>>,
+    ) -> Result<TrackBatchSummary, TrackError> {
+        let tracked = events.len();
+
+        let events = match self.prepare_batch(events) {
+            Ok(events) => events,
+            Err(err) => {
+                #[cfg(feature = "metrics")]
+                metrics::counter!("snowplow.events.failed").increment(tracked as u64);
+                return Err(err);
+            }
+        };
+
+        // The only events dropped while preparing the batch are the ones
+        // that can't be serialized.
+        let summary = TrackBatchSummary {
+            sent: events.len(),
+            unserializable: tracked - events.len(),
+            ..TrackBatchSummary::default()
+        };
+
+        if events.is_empty() {
+            return Ok(summary);
+        }
+
+        // Count what's actually sent; events dropped while preparing the
+        // batch have already been counted as dropped.
+        #[cfg(feature = "metrics")]
+        let count = summary.sent as u64;
+
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+
+        let result = self.sink.emit(EventContainer::new(events)).await;
+
+        #[cfg(feature = "metrics")]
+        {
+            metrics::histogram!("snowplow.flush.duration").record(start.elapsed());
+            match result {
+                Ok(()) => metrics::counter!("snowplow.events.sent").increment(count),
+                Err(_) => metrics::counter!("snowplow.events.failed").increment(count),
+            }
+        }
+
+        result.map(|()| summary)
+    }
+
+    /// Turn tracked events into the batch of [`SnowplowEvent`]s that's
+    /// emitted: attach and limit contexts, run the checks, and convert.
+    fn prepare_batch<Payload: HasSchema + Serialize>(
+        &self,
+        events: Vec<TrackedEvent<Payload>>,
+    ) -> Result<Vec<SnowplowEvent<'_, Encoded<'_, Payload>>>, TrackError> {
+        let now = SnowplowTimestamp::now();
+
+        let events: Vec<_> = events
+            .into_iter()
+            .map(|mut event| {
+                if let Some(defaults) = self.config.platform_contexts.get(&self.config.platform) {
+                    event.contexts.extend(defaults.iter().cloned());
+                }
+
+                let provided = self
+                    .context_providers
+                    .iter()
+                    .filter_map(|provider| provider());
+                event.contexts.extend(provided);
+                self.order_contexts(&mut event.contexts);
+                self.limit_contexts(&mut event.contexts);
+                for context in &mut event.contexts {
+                    let encoding = self.config.json_encoding_for(&context.schema);
+                    encoding.apply(&mut context.data);
+                }
+                event
+            })
+            .collect();
+
+        if self.config.platform == Platform::Mobile {
+            self.check_mobile_contexts(&events)?;
+        }
+
+        let mut events: Vec<_> = events
+            .into_iter()
+            .map(|event| {
+                let encoding = self.config.json_encoding_for(&event.payload.schema());
+                event
+                    .encoded(encoding)
+                    .into_snowplow_event(&self.config, now)
+            })
+            .collect();
+
+        if self.config.sort_by_timestamp {
+            // The sort is stable, so events with equal timestamps keep their
+            // tracked order.
+            events.sort_by_key(|event| SystemTime::from(event.created_timestamp));
+        }
+
+        if self.config.drop_unserializable_events || self.config.max_event_bytes.is_some() {
+            events = self.check_serialized_events(events)?;
+        }
+
+        Ok(events)
+    }
+
+    /// Track a self-describing event whose data is already available as JSON,
+    /// like an event received from a client that only needs forwarding. The
+    /// data is sent as-is, with the schema parsed from `schema_uri`, as in
+    /// `"iglu:com.acme/checkout/jsonschema/1-0-0"`. The only validation is
+    /// that the schema URI is well-formed and that the data is a JSON object.
+    pub async fn track_raw(
+        &self,
+        schema_uri: &str,
+        data: serde_json::Value,
+    ) -> Result<Tracked, TrackError> {
+        let schema = schema_uri.parse().map_err(TrackError::InvalidSchema)?;
+        if !data.is_object() {
+            return Err(TrackError::RawDataNotObject);
+        }
+
+        self.track(TrackedEvent::new(RawPayload { schema, data }))
+            .await
+    }
+
+    /// Track an [`AddToCartEvent`]. This is a shorthand for calling
+    /// [`track`][Tracker::track] with a default [`TrackedEvent`]. As with any
+    /// tracked event, if it has no currency, the configured
+    /// [`default_currency`][TrackerConfig::default_currency] is used.
+    pub async fn track_add_to_cart(&self, event: AddToCartEvent) -> Result<Tracked, TrackError> {
+        self.track(TrackedEvent::new(event)).await
+    }
+
+    /// Track a [`RemoveFromCartEvent`]. This is a shorthand for calling
+    /// [`track`][Tracker::track] with a default [`TrackedEvent`]. As with any
+    /// tracked event, if it has no currency, the configured
+    /// [`default_currency`][TrackerConfig::default_currency] is used.
+    pub async fn track_remove_from_cart(
+        &self,
+        event: RemoveFromCartEvent,
+    ) -> Result<Tracked, TrackError> {
+        self.track(TrackedEvent::new(event)).await
+    }
+
+    /// Serialize each event on its own, to enforce `max_event_bytes` and to
+    /// drop events that can't be serialized if `drop_unserializable_events`
+    /// is enabled.
+    fn check_serialized_events<'a, Payload: HasSchema + Serialize>(
+        &self,
+        events: Vec<SnowplowEvent<'a, Payload>>,
+    ) -> Result<Vec<SnowplowEvent<'a, Payload>>, TrackError> {
+        let mut checked = Vec::with_capacity(events.len());
+
+        #[cfg(feature = "metrics")]
+        let mut dropped = 0;
+
+        for event in events {
+            let mut counter = ByteCounter::default();
+            match serde_json::to_writer(&mut counter, &event) {
+                Err(err) if self.config.drop_unserializable_events => {
+                    tracing::warn!(
+                        error = %err,
+                        event_id = ?event.event_id,
+                        "Dropping event that can't be serialized"
+                    );
+
+                    #[cfg(feature = "metrics")]
+                    {
+                        dropped += 1;
+                    }
+                    continue;
+                }
+                // Otherwise, an event that can't be serialized fails the whole
+                // batch when the sink serializes it.
+                Err(_) => {}
+                Ok(()) => match self.config.max_event_bytes {
+                    Some(limit) if counter.0 > limit => {
+                        return Err(TrackError::EventTooLarge {
+                            size: counter.0,
+                            limit,
+                        });
+                    }
+                    _ => {}
+                },
+            }
+
+            checked.push(event);
+        }
+
+        // If the batch fails instead, its events are counted as failed.
+        #[cfg(feature = "metrics")]
+        metrics::counter!("snowplow.events.dropped").increment(dropped);
+
+        Ok(checked)
+    }
+
+    fn order_contexts(&self, contexts: &mut [SelfDescribingJson]) {
+        let priority = &self.config.context_priority;
+        if priority.is_empty() {
+            return;
+        }
+
+        // The sort is stable, so unlisted contexts, which all share the
+        // lowest priority, keep their relative order.
+        contexts.sort_by_key(|context| {
+            priority
+                .iter()
+                .position(|schema| {
+                    schema.vendor == context.schema.vendor && schema.name == context.schema.name
+                })
+                .unwrap_or(priority.len())
+        });
+    }
+
+    fn limit_contexts(&self, contexts: &mut Vec<SelfDescribingJson>) {
+        let Some(limit) = self.config.max_contexts_per_event else {
+            return;
+        };
+
+        if contexts.len() <= limit {
+            return;
+        }
+
+        let schemas: Vec<String> = contexts
+            .iter()
+            .map(|context| context.schema.to_string())
+            .collect();
+
+        tracing::warn!(
+            count = contexts.len(),
+            limit,
+            ?schemas,
+            "Event has more contexts than the configured limit"
+        );
+
+        if self.config.truncate_excess_contexts {
+            contexts.truncate(limit);
+        }
+    }
+
+    fn check_mobile_contexts<Payload: HasSchema + Serialize>(
+        &self,
+        events: &[TrackedEvent<Payload>],
+    ) -> Result<(), TrackError> {
+        let strictness = self.config.mobile_context_check;
+
+        if strictness == Strictness::Ignore {
+            return Ok(());
+        }
+
+        let is_missing = |event: &TrackedEvent<Payload>| {
+            !event.contexts.iter().any(|context| {
+                context.schema.vendor == "com.snowplowanalytics.snowplow"
+                    && context.schema.name == "mobile_context"
+            })
+        };
+
+        let missing = events.iter().filter(|event| is_missing(event)).count();
+
+        match (missing, strictness) {
+            (0, _) => Ok(()),
+            (_, Strictness::Deny) => Err(TrackError::MissingMobileContext),
+            (missing, _) => {
+                tracing::warn!(
+                    missing,
+                    "Tracking events on the mobile platform without a mobile context"
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The payload of an event tracked with [`Tracker::track_raw`], which
+/// serializes as its data alone, as a typed payload would.
+#[derive(Serialize)]
+#[serde(transparent)]
+struct RawPayload {
+    #[serde(skip)]
+    schema: Schema,
+    data: serde_json::Value,
+}
+
+impl HasSchema for RawPayload {
+    fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+}
+
+/// Remembers the fingerprints of recently tracked events, so that duplicates
+/// can be dropped.
+struct DedupFilter {
+    window: Duration,
+    encoding: JsonEncoding<'static>,
+    seen: Mutex<HashMap<u64, Instant>>,
+}
+
+impl DedupFilter {
+    fn new(window: Duration, encoding: JsonEncoding<'_>) -> Self {
+        Self {
+            window,
+            // Fingerprints always use sorted keys, so that maps with the same
+            // entries have the same fingerprint whatever their iteration order.
+            // Payloads are compared as tracked, before a default currency is
+            // filled in.
+            encoding: JsonEncoding {
+                sort_keys: true,
+                stringify_bools: encoding.stringify_bools,
+                default_currency: None,
+            },
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check if an identical payload was delivered within the window, or is
+    /// already in `pending`, the fingerprints of the batch being tracked. If
+    /// not, its fingerprint is added to `pending`, to be
+    /// [recorded][DedupFilter::record] once the batch is delivered.
+    fn is_duplicate<Payload: HasSchema + Serialize>(
+        &self,
+        payload: &Payload,
+        pending: &mut Vec<u64>,
+    ) -> bool {
+        // A payload that can't be serialized will fail to send anyway; let it
+        // through so that the error is reported.
+        let encoded = Encoded {
+            value: payload,
+            encoding: self.encoding,
+        };
+        let Ok(data) = serde_json::to_string(&encoded) else {
+            return false;
+        };
+
+        // DefaultHasher::new always uses the same keys, so fingerprints are
+        // stable.
+        let mut hasher = DefaultHasher::new();
+        payload.schema().to_string().hash(&mut hasher);
+        data.hash(&mut hasher);
+        let fingerprint = hasher.finish();
+
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap_or_else(PoisonError::into_inner);
+        seen.retain(|_, first_seen| now.duration_since(*first_seen) < self.window);
+
+        if seen.contains_key(&fingerprint) || pending.contains(&fingerprint) {
+            #[cfg(feature = "metrics")]
+            metrics::counter!("snowplow.events.dropped").increment(1);
+            true
+        } else {
+            pending.push(fingerprint);
+            false
+        }
+    }
+
+    /// Remember the fingerprints of delivered events, so that copies of them
+    /// tracked within the window are duplicates.
+    fn record(&self, fingerprints: &[u64]) {
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap_or_else(PoisonError::into_inner);
+        for &fingerprint in fingerprints {
+            seen.entry(fingerprint).or_insert(now);
+        }
+    }
+}
+
+/// Counts the events tracked with each schema in the current interval, to
+/// warn when a schema is tracked unexpectedly often.
+struct RateMonitor {
+    threshold: RateThreshold,
+    counts: Mutex<HashMap<String, SchemaCount>>,
+}
+
+struct SchemaCount {
+    interval_start: Instant,
+    events: u64,
+}
+
+impl RateMonitor {
+    fn new(threshold: RateThreshold) -> Self {
+        Self {
+            threshold,
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn record(&self, schemas: impl IntoIterator<Item = Schema>) {
+        let now = Instant::now();
+        let mut counts = self.counts.lock().unwrap_or_else(PoisonError::into_inner);
+
+        for schema in schemas {
+            let count = counts.entry(schema.to_string()).or_insert(SchemaCount {
+                interval_start: now,
+                events: 0,
+            });
+
+            if now.duration_since(count.interval_start) >= self.threshold.interval {
+                count.interval_start = now;
+                count.events = 0;
+            }
+
+            count.events += 1;
+
+            // Only warn when the threshold is first crossed, so there's at
+            // most one warning per interval.
+            if count.events == self.threshold.events.saturating_add(1) {
+                tracing::warn!(
+                    %schema,
+                    limit = self.threshold.events,
+                    interval = ?self.threshold.interval,
+                    "Events with this schema are being tracked unexpectedly often"
+                );
+            }
+        }
+    }
+}
+
+/// An event to be sent to the tracker. Mostly this is a vehicle for your
+/// Unstructured payload, but also allows you to include your own fields for
+/// the top-level snowplow event
+#[derive(Debug, Clone, Default)]
+pub struct TrackedEvent<T: HasSchema + Serialize> {
+    /// Your specific event payload. The tracker will handle correctly wrapping
+    /// and encoding this according to the Snowplow protocol, so all you need
     /// to provide is your own data.
     ///
     /// The payload needs to implement `Serialize`, so it can be encoded as a
@@ -158,7 +1620,28 @@ pub struct TrackedEvent<T: HasSchema + Serialize> {
     /// your batching scheme imposes delay between when the event occurs and
     /// when it's tracked.
     pub timestamp: Option<SnowplowTimestamp>,
-    // TODO: Contexts
+
+    /// The moment when this event was sent to the collector. If omitted, we
+    /// will use the moment that `track` is called. You should only set this
+    /// when replaying historical events, such as when backfilling them from
+    /// logs, so that the original send time is preserved.
+    pub sent_timestamp: Option<SnowplowTimestamp>,
+
+    /// Context entities to attach to this event. These describe the
+    /// circumstances in which the event occurred, like the product being
+    /// viewed or the current state of a shopping cart.
+    pub contexts: Vec<SelfDescribingJson>,
+
+    /// The app ID to use for this event (the `aid` field), instead of the
+    /// tracker's [`app_id`][TrackerConfig::app_id]. If omitted, the tracker's
+    /// app ID is used. This is useful when a single tracker serves several
+    /// apps.
+    pub app_id: Option<String>,
+
+    /// The event type to send this event as (the `e` field). This is
+    /// [`EventType::SelfDescribingEvent`] by default; other types are only
+    /// useful with collectors that recognize them.
+    pub event_type: EventType,
 }
 
 impl<T: HasSchema + Serialize> TrackedEvent<T> {
@@ -168,6 +1651,1435 @@ impl<T: HasSchema + Serialize> TrackedEvent<T> {
             payload,
             id: None,
             timestamp: None,
+            sent_timestamp: None,
+            contexts: Vec::new(),
+            app_id: None,
+            event_type: EventType::SelfDescribingEvent,
+        }
+    }
+
+    /// Set the moment when this event occurred (the `dtm` field). See
+    /// [`timestamp`][TrackedEvent::timestamp].
+    pub fn with_timestamp(mut self, timestamp: impl Into<SnowplowTimestamp>) -> Self {
+        self.timestamp = Some(timestamp.into());
+        self
+    }
+
+    /// Set the moment when this event was sent (the `stm` field), instead of
+    /// using the moment it's tracked. See
+    /// [`sent_timestamp`][TrackedEvent::sent_timestamp].
+    pub fn with_sent_timestamp(mut self, timestamp: impl Into<SnowplowTimestamp>) -> Self {
+        self.sent_timestamp = Some(timestamp.into());
+        self
+    }
+
+    /// Override the tracker's app ID for this event. See
+    /// [`app_id`][TrackedEvent::app_id].
+    pub fn with_app_id(mut self, app_id: impl Into<String>) -> Self {
+        self.app_id = Some(app_id.into());
+        self
+    }
+
+    /// Send this event with a different event type. See
+    /// [`event_type`][TrackedEvent::event_type].
+    pub fn with_event_type(mut self, event_type: EventType) -> Self {
+        self.event_type = event_type;
+        self
+    }
+
+    /// Convert this event to the [`SnowplowEvent`] that's sent to the
+    /// collector, exactly as a [`Tracker`] with the given `config` does,
+    /// with `now` as the moment it's tracked. This is useful for custom
+    /// [`EventSink`]s or tools that need to produce the wire format without
+    /// a tracker.
+    ///
+    /// Only the event's own contexts are included; attaching the
+    /// [`platform_contexts`][TrackerConfig::platform_contexts] and the
+    /// contexts from context providers, limiting the number of contexts, and
+    /// the encoding options [`sort_keys`][TrackerConfig::sort_keys] and
+    /// [`stringify_bools`][TrackerConfig::stringify_bools], happen in the
+    /// tracker before this conversion.
+    pub fn into_snowplow_event(
+        self,
+        config: &TrackerConfig,
+        now: SnowplowTimestamp,
+    ) -> SnowplowEvent<'_, T> {
+        let created_timestamp = self.timestamp.unwrap_or(now);
+        let sent_timestamp = match (self.sent_timestamp, config.rewrite_sent_timestamp) {
+            (Some(sent_timestamp), _) => sent_timestamp,
+            (None, true) => now,
+            (None, false) => created_timestamp,
+        };
+
+        let (created_timestamp, sent_timestamp) = match config.clock_skew {
+            Some(skew) => (skew.apply(created_timestamp), skew.apply(sent_timestamp)),
+            None => (created_timestamp, sent_timestamp),
+        };
+
+        let contexts = (!self.contexts.is_empty()).then(|| {
+            let contexts = Contexts::new(self.contexts);
+            let contexts = match config.contexts_schema_version {
+                Some(version) => Contexts {
+                    version,
+                    ..contexts
+                },
+                None => contexts,
+            };
+
+            JsonString(Envelope(contexts))
+        });
+
+        SnowplowEvent {
+            event_type: self.event_type,
+            payload: JsonString(PayloadWrapper::new(self.payload)),
+            contexts,
+            platform: config.platform,
+            app_id: match self.app_id {
+                Some(app_id) => Cow::Owned(app_id),
+                None => Cow::Borrowed(&config.app_id),
+            },
+            tracker_id: TRACKER_ID,
+            namespace: &config.namespace,
+            event_id: self.id,
+            created_timestamp,
+            sent_timestamp,
+        }
+    }
+
+    /// Wrap the payload of this event, so that it's serialized with the
+    /// given encoding.
+    fn encoded(self, encoding: JsonEncoding<'_>) -> TrackedEvent<Encoded<'_, T>> {
+        TrackedEvent {
+            payload: Encoded {
+                value: self.payload,
+                encoding,
+            },
+            id: self.id,
+            timestamp: self.timestamp,
+            sent_timestamp: self.sent_timestamp,
+            contexts: self.contexts,
+            app_id: self.app_id,
+            event_type: self.event_type,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::sink::NoopSink;
+    use crate::testing::{
+        self, capture_warnings, contexts_of, unstruct_data, MockCollector, MockResponse,
+        RecordingSink, TestEvent,
+    };
+
+    fn entity(name: &'static str) -> SelfDescribingJson {
+        SelfDescribingJson::new(
+            Schema::new("com.example", name, SchemaVersion::new(1, 0, 0)),
+            json!({}),
+        )
+    }
+
+    fn mobile_context() -> SelfDescribingJson {
+        SelfDescribingJson::new(
+            Schema::new_snowplow("mobile_context", SchemaVersion::new(1, 0, 3)),
+            json!({"osType": "ios"}),
+        )
+    }
+
+    fn context_names(event: &serde_json::Value) -> Vec<String> {
+        contexts_of(event)
+            .iter()
+            .map(|context| context["schema"].as_str().unwrap().to_owned())
+            .map(|schema| schema.split('/').nth(1).unwrap().to_owned())
+            .collect()
+    }
+
+    fn at_millis(millis: u64) -> SnowplowTimestamp {
+        (UNIX_EPOCH + Duration::from_millis(millis)).into()
+    }
+
+    #[tokio::test]
+    async fn tracks_event_through_emitter() {
+        let collector = MockCollector::start(MockResponse::ok()).await;
+        let emitter = Emitter::builder(collector.url())
+            .upgrade_to_https(false)
+            .build()
+            .unwrap();
+        let tracker = Tracker::new(emitter, testing::config()).unwrap();
+
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 7 }))
+            .await
+            .unwrap();
+
+        let requests = collector.requests();
+        assert_eq!(requests.len(), 1);
+        let event = &requests[0].json()["data"][0];
+        assert_eq!(event["e"], "ue");
+        assert_eq!(event["aid"], "app_id");
+        assert_eq!(event["tna"], "ns");
+        assert_eq!(event["p"], "app");
+        assert_eq!(event["tv"], TRACKER_ID);
+        assert_eq!(
+            unstruct_data(event),
+            json!({"schema": "iglu:com.example/test_event/jsonschema/1-0-0", "data": {"id": 7}})
+        );
+    }
+
+    #[tokio::test]
+    async fn generic_tracker_can_track_from_a_spawned_task() {
+        async fn track_in_task<S: EventSink + Send + Sync + 'static>(
+            tracker: std::sync::Arc<Tracker<S>>,
+        ) -> Result<Tracked, TrackError> {
+            tokio::spawn(async move { tracker.track(TrackedEvent::new(TestEvent { id: 1 })).await })
+                .await
+                .unwrap()
+        }
+
+        let tracker =
+            std::sync::Arc::new(Tracker::new(RecordingSink::default(), testing::config()).unwrap());
+        track_in_task(std::sync::Arc::clone(&tracker))
+            .await
+            .unwrap();
+        assert_eq!(tracker.sink().events().len(), 1);
+    }
+
+    #[test]
+    fn builder_matches_struct_literal() {
+        let schema = Schema::new("com.example", "runtime", SchemaVersion::new(1, 0, 0));
+        let built = TrackerConfig::builder()
+            .namespace("ns")
+            .platform(Platform::Web)
+            .app_id("app")
+            .default_currency("EUR")
+            .dedup_window(Duration::from_secs(1))
+            .mobile_context_check(Strictness::Deny)
+            .empty_identifier_check(Strictness::Ignore)
+            .max_contexts_per_event(5)
+            .truncate_excess_contexts(true)
+            .max_event_bytes(1000)
+            .drop_unserializable_events(true)
+            .platform_context(Platform::Web, entity("page"))
+            .runtime_context_schema(schema.clone())
+            .rewrite_sent_timestamp(false)
+            .sort_by_timestamp(true)
+            .sort_keys(true)
+            .stringify_bools(true)
+            .clock_skew(ClockSkew::Forward(Duration::from_secs(5)))
+            .contexts_schema_version(SchemaVersion::new(1, 0, 0))
+            .disabled(true)
+            .build();
+
+        let literal = TrackerConfig {
+            namespace: "ns".to_owned(),
+            platform: Platform::Web,
+            app_id: "app".to_owned(),
+            default_currency: Some("EUR".to_owned()),
+            dedup_window: Some(Duration::from_secs(1)),
+            mobile_context_check: Strictness::Deny,
+            empty_identifier_check: Strictness::Ignore,
+            max_contexts_per_event: Some(5),
+            truncate_excess_contexts: true,
+            max_event_bytes: Some(1000),
+            drop_unserializable_events: true,
+            platform_contexts: [(Platform::Web, vec![entity("page")])]
+                .into_iter()
+                .collect(),
+            runtime_context_schema: Some(schema),
+            rewrite_sent_timestamp: false,
+            sort_by_timestamp: true,
+            sort_keys: true,
+            stringify_bools: true,
+            clock_skew: Some(ClockSkew::Forward(Duration::from_secs(5))),
+            contexts_schema_version: Some(SchemaVersion::new(1, 0, 0)),
+            disabled: true,
+            ..TrackerConfig::default()
+        };
+
+        assert_eq!(format!("{built:?}"), format!("{literal:?}"));
+    }
+
+    #[test]
+    fn config_validation() {
+        let valid = Tracker::new(NoopSink, testing::config());
+        assert!(valid.is_ok());
+
+        let config = TrackerConfig {
+            namespace: "web tracker".to_owned(),
+            ..testing::config()
+        };
+        assert!(matches!(
+            Tracker::new(NoopSink, config),
+            Err(ConfigError::InvalidNamespace(namespace)) if namespace == "web tracker"
+        ));
+
+        let config = TrackerConfig {
+            default_currency: Some("usd".to_owned()),
+            ..testing::config()
+        };
+        assert!(matches!(
+            Tracker::new(NoopSink, config),
+            Err(ConfigError::InvalidCurrency(currency)) if currency == "usd"
+        ));
+    }
+
+    #[test]
+    fn empty_identifier_check() {
+        let config = |strictness| TrackerConfig {
+            app_id: String::new(),
+            empty_identifier_check: strictness,
+            ..testing::config()
+        };
+
+        let (_guard, warnings) = capture_warnings();
+        assert!(Tracker::new(NoopSink, config(Strictness::Ignore)).is_ok());
+        assert!(warnings.messages().is_empty());
+
+        assert!(Tracker::new(NoopSink, config(Strictness::Warn)).is_ok());
+        assert_eq!(warnings.messages().len(), 1);
+
+        assert!(matches!(
+            Tracker::new(NoopSink, config(Strictness::Deny)),
+            Err(ConfigError::EmptyIdentifier("app_id"))
+        ));
+    }
+
+    #[test]
+    fn config_from_env() {
+        // This is the only test that touches these variables
+        let vars = [
+            (
+                "SNOWPLOW_COLLECTOR_URL",
+                "https://collector.example.com/com.snowplowanalytics.snowplow/tp2",
+            ),
+            ("SNOWPLOW_NAMESPACE", "env-ns"),
+            ("SNOWPLOW_APP_ID", "env-app"),
+            ("SNOWPLOW_PLATFORM", "srv"),
+            ("SNOWPLOW_DEFAULT_CURRENCY", "GBP"),
+        ];
+        for (name, value) in vars {
+            std::env::set_var(name, value);
+        }
+
+        let config = TrackerConfig::from_env().unwrap();
+        assert_eq!(config.namespace, "env-ns");
+        assert_eq!(config.app_id, "env-app");
+        assert_eq!(config.platform, Platform::ServerSide);
+        assert_eq!(config.default_currency.as_deref(), Some("GBP"));
+
+        let tracker = Tracker::from_env().unwrap();
+        assert_eq!(tracker.namespace(), "env-ns");
+        assert_eq!(tracker.config.app_id, "env-app");
+        assert_eq!(
+            tracker.sink().collector_url().as_str(),
+            "https://collector.example.com/com.snowplowanalytics.snowplow/tp2"
+        );
+
+        std::env::set_var("SNOWPLOW_COLLECTOR_URL", "not a url");
+        assert!(matches!(
+            Tracker::from_env(),
+            Err(ConfigError::InvalidCollectorUrl(_))
+        ));
+
+        std::env::remove_var("SNOWPLOW_COLLECTOR_URL");
+        assert!(matches!(
+            Tracker::from_env(),
+            Err(ConfigError::MissingEnvVar("SNOWPLOW_COLLECTOR_URL"))
+        ));
+        // The collector URL is only required by the tracker
+        assert!(TrackerConfig::from_env().is_ok());
+
+        std::env::set_var("SNOWPLOW_PLATFORM", "desktop");
+        assert!(matches!(
+            TrackerConfig::from_env(),
+            Err(ConfigError::InvalidEnvVar { name: "SNOWPLOW_PLATFORM", value }) if value == "desktop"
+        ));
+
+        std::env::remove_var("SNOWPLOW_PLATFORM");
+        std::env::remove_var("SNOWPLOW_APP_ID");
+        assert!(matches!(
+            TrackerConfig::from_env(),
+            Err(ConfigError::MissingEnvVar("SNOWPLOW_APP_ID"))
+        ));
+
+        for (name, _) in vars {
+            std::env::remove_var(name);
+        }
+    }
+
+    #[tokio::test]
+    async fn non_finite_floats_are_sent_as_null() {
+        #[derive(Serialize)]
+        struct Measurement {
+            value: f64,
+            limit: f64,
+        }
+
+        impl HasSchema for Measurement {
+            fn schema(&self) -> Schema {
+                Schema::new("com.example", "measurement", SchemaVersion::new(1, 0, 0))
+            }
+        }
+
+        let sink = RecordingSink::default();
+        let tracker = Tracker::new(&sink, testing::config()).unwrap();
+
+        let mut event = TrackedEvent::new(Measurement {
+            value: f64::NAN,
+            limit: f64::INFINITY,
+        });
+        let context = Measurement {
+            value: 1.5,
+            limit: f64::NEG_INFINITY,
+        };
+        event
+            .contexts
+            .push(SelfDescribingJson::from_entity(&context).unwrap());
+        tracker.track(event).await.unwrap();
+
+        let event = &sink.events()[0];
+        assert_eq!(
+            unstruct_data(event)["data"],
+            json!({"value": null, "limit": null})
+        );
+        assert_eq!(
+            contexts_of(event)[0]["data"],
+            json!({"value": 1.5, "limit": null})
+        );
+    }
+
+    #[tokio::test]
+    async fn dedup_drops_identical_events_within_window() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            dedup_window: Some(Duration::from_secs(60)),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+        let track = |id| tracker.track(TrackedEvent::new(TestEvent { id }));
+
+        assert_eq!(track(1).await.unwrap(), Tracked::Sent);
+        assert_eq!(
+            track(1).await.unwrap(),
+            Tracked::Dropped(DropReason::Duplicate)
+        );
+        assert_eq!(track(2).await.unwrap(), Tracked::Sent);
+
+        let ids: Vec<_> = sink
+            .events()
+            .iter()
+            .map(|event| unstruct_data(event)["data"]["id"].clone())
+            .collect();
+        assert_eq!(ids, [json!(1), json!(2)]);
+    }
+
+    #[tokio::test]
+    async fn dedup_forgets_events_that_fail_to_send() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            dedup_window: Some(Duration::from_secs(60)),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        sink.set_failing(true);
+        assert!(tracker
+            .track(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .is_err());
+
+        sink.set_failing(false);
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .unwrap();
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .unwrap();
+        assert_eq!(sink.events().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn dedup_forgets_rejected_events() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            dedup_window: Some(Duration::from_secs(60)),
+            platform: Platform::Mobile,
+            mobile_context_check: Strictness::Deny,
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        assert!(matches!(
+            tracker.track(TrackedEvent::new(TestEvent { id: 1 })).await,
+            Err(TrackError::MissingMobileContext)
+        ));
+
+        // Contexts aren't part of the fingerprint, so this is the same event
+        let mut event = TrackedEvent::new(TestEvent { id: 1 });
+        event.contexts.push(mobile_context());
+        tracker.track(event).await.unwrap();
+        assert_eq!(sink.events().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn dedup_ignores_map_key_order() {
+        #[derive(Serialize)]
+        struct Counts(HashMap<String, u32>);
+
+        impl HasSchema for Counts {
+            fn schema(&self) -> Schema {
+                TestEvent { id: 0 }.schema()
+            }
+        }
+
+        // Every HashMap has its own random iteration order
+        let counts = || Counts((0..32).map(|n| (format!("key{n}"), n)).collect());
+
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            dedup_window: Some(Duration::from_secs(60)),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        tracker
+            .track_batch([TrackedEvent::new(counts()), TrackedEvent::new(counts())])
+            .await
+            .unwrap();
+        for _ in 0..10 {
+            tracker.track(TrackedEvent::new(counts())).await.unwrap();
+        }
+
+        assert_eq!(sink.events().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn dedup_window_expires() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            dedup_window: Some(Duration::from_millis(50)),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .unwrap();
+
+        assert_eq!(sink.events().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn mobile_context_check() {
+        let sink = RecordingSink::default();
+        let config = |strictness| TrackerConfig {
+            platform: Platform::Mobile,
+            mobile_context_check: strictness,
+            ..testing::config()
+        };
+
+        let (_guard, warnings) = capture_warnings();
+        let tracker = Tracker::new(&sink, config(Strictness::Warn)).unwrap();
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .unwrap();
+        assert_eq!(sink.events().len(), 1);
+        assert_eq!(warnings.messages().len(), 1);
+
+        let mut event = TrackedEvent::new(TestEvent { id: 2 });
+        event.contexts.push(mobile_context());
+        tracker.track(event).await.unwrap();
+        assert_eq!(warnings.messages().len(), 1);
+
+        let tracker = Tracker::new(&sink, config(Strictness::Deny)).unwrap();
+        assert!(matches!(
+            tracker.track(TrackedEvent::new(TestEvent { id: 3 })).await,
+            Err(TrackError::MissingMobileContext)
+        ));
+        assert_eq!(sink.events().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn platform_contexts_are_attached() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig::builder()
+            .namespace("ns")
+            .app_id("app")
+            .platform(Platform::Mobile)
+            .platform_context(Platform::Mobile, mobile_context())
+            .platform_context(Platform::Web, entity("web_page"))
+            .mobile_context_check(Strictness::Deny)
+            .build();
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .unwrap();
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 2 }))
+            .await
+            .unwrap();
+
+        for event in sink.events() {
+            assert_eq!(context_names(&event), ["mobile_context"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn context_providers_are_called_per_event() {
+        let sink = RecordingSink::default();
+        let mut tracker = Tracker::new(&sink, testing::config()).unwrap();
+
+        let counter = std::sync::atomic::AtomicU32::new(0);
+        tracker.add_context_provider(move || {
+            let trace_id = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Some(SelfDescribingJson::new(
+                Schema::new("com.example", "trace", SchemaVersion::new(1, 0, 0)),
+                json!({"traceId": trace_id}),
+            ))
+        });
+        tracker.add_context_provider(|| None);
+
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .unwrap();
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 2 }))
+            .await
+            .unwrap();
+
+        let trace_ids: Vec<_> = sink
+            .events()
+            .iter()
+            .map(|event| {
+                let contexts = contexts_of(event);
+                assert_eq!(contexts.len(), 1);
+                contexts[0]["data"]["traceId"].clone()
+            })
+            .collect();
+        assert_eq!(trace_ids, [json!(0), json!(1)]);
+    }
+
+    #[tokio::test]
+    async fn runtime_and_instance_contexts_are_attached() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            runtime_context_schema: Some(Schema::new(
+                "com.example",
+                "runtime",
+                SchemaVersion::new(1, 0, 0),
+            )),
+            instance_context_schema: Some(Schema::new(
+                "com.example",
+                "instance",
+                SchemaVersion::new(1, 0, 0),
+            )),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .unwrap();
+
+        let contexts = contexts_of(&sink.events()[0]);
+        assert_eq!(contexts.len(), 2);
+        assert_eq!(contexts[0]["data"]["os"], std::env::consts::OS);
+        assert_eq!(contexts[0]["data"]["arch"], std::env::consts::ARCH);
+        assert!(!contexts[1]["data"]["hostname"].as_str().unwrap().is_empty());
+        assert_eq!(contexts[1]["data"]["pid"], std::process::id());
+    }
+
+    #[tokio::test]
+    async fn excess_contexts_warn_and_truncate() {
+        let sink = RecordingSink::default();
+        let event = || {
+            let mut event = TrackedEvent::new(TestEvent { id: 1 });
+            event.contexts = (0..100).map(|_| entity("entity")).collect();
+            event
+        };
+
+        let (_guard, warnings) = capture_warnings();
+        let config = TrackerConfig {
+            max_contexts_per_event: Some(10),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+        tracker.track(event()).await.unwrap();
+        assert_eq!(warnings.messages().len(), 1);
+        assert_eq!(contexts_of(&sink.events()[0]).len(), 100);
+
+        let config = TrackerConfig {
+            max_contexts_per_event: Some(10),
+            truncate_excess_contexts: true,
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+        tracker.track(event()).await.unwrap();
+        assert_eq!(warnings.messages().len(), 2);
+        assert_eq!(contexts_of(&sink.events()[1]).len(), 10);
+    }
+
+    #[tokio::test]
+    async fn context_priority() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            context_priority: vec![
+                Schema::new_snowplow("client_session", SchemaVersion::new(1, 0, 0)),
+                Schema::new("com.example", "user", SchemaVersion::new(1, 0, 0)),
+            ],
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        let session = SelfDescribingJson::new(
+            Schema::new_snowplow("client_session", SchemaVersion::new(1, 0, 2)),
+            json!({}),
+        );
+        let mut event = TrackedEvent::new(TestEvent { id: 1 });
+        event.contexts = vec![entity("a"), entity("user"), entity("b"), session];
+        tracker.track(event).await.unwrap();
+
+        assert_eq!(
+            context_names(&sink.events()[0]),
+            ["client_session", "user", "a", "b"]
+        );
+    }
+
+    #[tokio::test]
+    async fn contexts_schema_version_override() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            contexts_schema_version: Some(SchemaVersion::new(1, 0, 0)),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        let mut event = TrackedEvent::new(TestEvent { id: 1 });
+        event.contexts.push(entity("a"));
+        tracker.track(event).await.unwrap();
+
+        let co: serde_json::Value =
+            serde_json::from_str(sink.events()[0]["co"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            co["schema"],
+            "iglu:com.snowplowanalytics.snowplow/contexts/jsonschema/1-0-0"
+        );
+    }
+
+    #[tokio::test]
+    async fn replayed_timestamps_are_preserved() {
+        let sink = RecordingSink::default();
+        let tracker = Tracker::new(&sink, testing::config()).unwrap();
+
+        let event = TrackedEvent::new(TestEvent { id: 1 })
+            .with_timestamp(at_millis(1_000))
+            .with_sent_timestamp(at_millis(2_000));
+        tracker.track(event).await.unwrap();
+
+        let event = &sink.events()[0];
+        assert_eq!(event["dtm"], "1000");
+        assert_eq!(event["stm"], "2000");
+    }
+
+    #[tokio::test]
+    async fn sent_timestamp_is_set_at_track_time() {
+        let sink = RecordingSink::default();
+        let tracker = Tracker::new(&sink, testing::config()).unwrap();
+
+        let event = TrackedEvent::new(TestEvent { id: 1 }).with_timestamp(at_millis(1_000));
+        tracker.track(event).await.unwrap();
+
+        let event = &sink.events()[0];
+        assert_eq!(event["dtm"], "1000");
+        let stm: u64 = event["stm"].as_str().unwrap().parse().unwrap();
+        assert!(stm > 1_000);
+    }
+
+    #[tokio::test]
+    async fn disabled_sent_timestamp_rewriting() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            rewrite_sent_timestamp: false,
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        let event = TrackedEvent::new(TestEvent { id: 1 }).with_timestamp(at_millis(1_000));
+        tracker.track(event).await.unwrap();
+        tracker
+            .track(TrackedEvent::new(TestEvent { id: 2 }))
+            .await
+            .unwrap();
+
+        let events = sink.events();
+        assert_eq!(events[0]["stm"], "1000");
+        assert_eq!(events[1]["stm"], events[1]["dtm"]);
+    }
+
+    #[tokio::test]
+    async fn clock_skew_shifts_timestamps() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            clock_skew: Some(ClockSkew::Forward(Duration::from_secs(5))),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        let event = TrackedEvent::new(TestEvent { id: 1 })
+            .with_timestamp(at_millis(10_000))
+            .with_sent_timestamp(at_millis(11_000));
+        tracker.track(event).await.unwrap();
+
+        let event = &sink.events()[0];
+        assert_eq!(event["dtm"], "15000");
+        assert_eq!(event["stm"], "16000");
+
+        let backward = ClockSkew::Backward(Duration::from_secs(5)).apply(at_millis(10_000));
+        assert_eq!(backward, at_millis(5_000));
+    }
+
+    #[tokio::test]
+    async fn track_with_id_returns_event_id() {
+        let sink = RecordingSink::default();
+        let tracker = Tracker::new(&sink, testing::config()).unwrap();
+
+        let (generated, tracked) = tracker
+            .track_with_id(TrackedEvent::new(TestEvent { id: 1 }))
+            .await
+            .unwrap();
+        assert_eq!(tracked, Tracked::Sent);
+
+        let explicit = Uuid::new_v4();
+        let mut event = TrackedEvent::new(TestEvent { id: 2 });
+        event.id = Some(explicit);
+        let returned = tracker.track_with_id(event).await.unwrap();
+        assert_eq!(returned, (explicit, Tracked::Sent));
+
+        let events = sink.events();
+        assert_eq!(events[0]["eid"], generated.to_string());
+        assert_eq!(events[1]["eid"], explicit.to_string());
+        assert_eq!(generated.get_version_num(), 4);
+    }
+
+    #[cfg(feature = "uuid-v7")]
+    #[test]
+    fn v7_event_ids_are_time_ordered() {
+        let ids: Vec<_> = (0..100).map(|_| EventIdVersion::V7.generate()).collect();
+        assert!(ids.iter().all(|id| id.get_version_num() == 7));
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[tokio::test]
+    async fn app_id_override() {
+        let sink = RecordingSink::default();
+        let tracker = Tracker::new(&sink, testing::config()).unwrap();
+
+        tracker
+            .track_batch([
+                TrackedEvent::new(TestEvent { id: 1 }).with_app_id("first-app"),
+                TrackedEvent::new(TestEvent { id: 2 }).with_app_id("second-app"),
+                TrackedEvent::new(TestEvent { id: 3 }),
+            ])
+            .await
+            .unwrap();
+
+        let app_ids: Vec<_> = sink
+            .events()
+            .iter()
+            .map(|event| event["aid"].clone())
+            .collect();
+        assert_eq!(app_ids, ["first-app", "second-app", "app_id"]);
+    }
+
+    #[tokio::test]
+    async fn custom_event_type() {
+        let sink = RecordingSink::default();
+        let tracker = Tracker::new(&sink, testing::config()).unwrap();
+
+        tracker
+            .track_batch([
+                TrackedEvent::new(TestEvent { id: 1 })
+                    .with_event_type(EventType::Custom("xe".into())),
+                TrackedEvent::new(TestEvent { id: 2 }),
+            ])
+            .await
+            .unwrap();
+
+        let event_types: Vec<_> = sink
+            .events()
+            .iter()
+            .map(|event| event["e"].clone())
+            .collect();
+        assert_eq!(event_types, ["xe", "ue"]);
+    }
+
+    #[tokio::test]
+    async fn max_event_bytes_rejects_large_events() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            max_event_bytes: Some(1024 * 1024),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        let blob = "x".repeat(3 * 1024 * 1024);
+        let result = tracker
+            .track_batch([
+                TrackedEvent::new(RawPayload {
+                    schema: TestEvent { id: 0 }.schema(),
+                    data: json!({"id": 1}),
+                }),
+                TrackedEvent::new(RawPayload {
+                    schema: TestEvent { id: 0 }.schema(),
+                    data: json!({"blob": blob}),
+                }),
+            ])
+            .await;
+
+        match result {
+            Err(TrackError::EventTooLarge { size, limit }) => {
+                assert!(size > 3 * 1024 * 1024);
+                assert_eq!(limit, 1024 * 1024);
+            }
+            result => panic!("expected EventTooLarge, got {result:?}"),
+        }
+        assert!(sink.batches().is_empty());
+    }
+
+    #[tokio::test]
+    async fn track_raw_sends_data_as_is() {
+        let sink = RecordingSink::default();
+        let tracker = Tracker::new(&sink, testing::config()).unwrap();
+
+        tracker
+            .track_raw(
+                "iglu:com.acme/checkout/jsonschema/1-0-0",
+                json!({"total": 10, "items": ["a"]}),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            unstruct_data(&sink.events()[0]),
+            json!({
+                "schema": "iglu:com.acme/checkout/jsonschema/1-0-0",
+                "data": {"total": 10, "items": ["a"]},
+            })
+        );
+
+        assert!(matches!(
+            tracker.track_raw("com.acme/checkout", json!({})).await,
+            Err(TrackError::InvalidSchema(SchemaError::InvalidUri))
+        ));
+        assert!(matches!(
+            tracker
+                .track_raw("iglu:com.acme/checkout/jsonschema/1-0-0", json!([1]))
+                .await,
+            Err(TrackError::RawDataNotObject)
+        ));
+        assert_eq!(sink.events().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn default_currency_applies_to_ecommerce_events() {
+        use crate::entities::{CartEntity, ProductEntity};
+        use crate::events::CheckoutStepEvent;
+
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            default_currency: Some("EUR".to_owned()),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        tracker
+            .track_add_to_cart(AddToCartEvent::default())
+            .await
+            .unwrap();
+        tracker
+            .track(TrackedEvent::new(RemoveFromCartEvent::default()))
+            .await
+            .unwrap();
+
+        // A currency set on the event takes precedence
+        tracker
+            .track(TrackedEvent::new(AddToCartEvent {
+                currency: Some("USD".to_owned()),
+                ..AddToCartEvent::default()
+            }))
+            .await
+            .unwrap();
+
+        let mut checkout = TrackedEvent::new(CheckoutStepEvent {
+            step: 1,
+            ..CheckoutStepEvent::default()
+        });
+        checkout
+            .contexts
+            .push(SelfDescribingJson::from_entity(&CartEntity::default()).unwrap());
+        checkout.contexts.push(
+            SelfDescribingJson::from_entity(&ProductEntity {
+                currency: "GBP".to_owned(),
+                ..ProductEntity::default()
+            })
+            .unwrap(),
+        );
+        tracker.track(checkout).await.unwrap();
+
+        tracker
+            .track_raw(
+                "iglu:com.snowplowanalytics.snowplow/add_to_cart/jsonschema/1-0-0",
+                json!({"sku": "abc", "unitPrice": 1.5, "quantity": 1}),
+            )
+            .await
+            .unwrap();
+
+        // Other schemas are left alone, even with a currency field
+        tracker
+            .track_raw(
+                "iglu:com.example/purchase/jsonschema/1-0-0",
+                json!({"currency": ""}),
+            )
+            .await
+            .unwrap();
+
+        let events = sink.events();
+        let currency = |index: usize| unstruct_data(&events[index])["data"]["currency"].clone();
+        assert_eq!(currency(0), "EUR");
+        assert_eq!(currency(1), "EUR");
+        assert_eq!(currency(2), "USD");
+        assert_eq!(currency(4), "EUR");
+        assert_eq!(currency(5), "");
+
+        let contexts = contexts_of(&events[3]);
+        assert_eq!(contexts[0]["data"]["currency"], "EUR");
+        assert_eq!(contexts[1]["data"]["currency"], "GBP");
+    }
+
+    #[tokio::test]
+    async fn into_snowplow_event_matches_tracker() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            rewrite_sent_timestamp: false,
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config.clone()).unwrap();
+
+        let event = || {
+            let mut event = TrackedEvent::new(TestEvent { id: 1 })
+                .with_timestamp(at_millis(1_000))
+                .with_app_id("other-app");
+            event.id = Some(Uuid::nil());
+            event.contexts.push(entity("a"));
+            event
+        };
+
+        tracker.track(event()).await.unwrap();
+
+        let converted = event().into_snowplow_event(&config, SnowplowTimestamp::now());
+        assert_eq!(serde_json::to_value(converted).unwrap(), sink.events()[0]);
+    }
+
+    #[tokio::test]
+    async fn sort_by_timestamp() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            sort_by_timestamp: true,
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        tracker
+            .track_batch([3_000, 1_000, 2_000, 1_000].into_iter().enumerate().map(
+                |(id, millis)| {
+                    TrackedEvent::new(TestEvent { id: id as u32 }).with_timestamp(at_millis(millis))
+                },
+            ))
+            .await
+            .unwrap();
+
+        let order: Vec<_> = sink
+            .events()
+            .iter()
+            .map(|event| unstruct_data(event)["data"]["id"].as_u64().unwrap())
+            .collect();
+        assert_eq!(order, [1, 3, 2, 0]);
+    }
+
+    #[tokio::test]
+    async fn sort_keys_covers_payloads_and_contexts() {
+        #[derive(Serialize)]
+        struct Letters(HashMap<char, u32>);
+
+        impl HasSchema for Letters {
+            fn schema(&self) -> Schema {
+                TestEvent { id: 0 }.schema()
+            }
         }
+
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            sort_keys: true,
+            platform_contexts: HashMap::from([(
+                Platform::default(),
+                vec![SelfDescribingJson::new(
+                    entity("platform").schema,
+                    json!({"z": 1, "a": 2}),
+                )],
+            )]),
+            ..testing::config()
+        };
+        let mut tracker = Tracker::new(&sink, config).unwrap();
+        tracker.add_context_provider(|| {
+            Some(SelfDescribingJson::new(
+                entity("provided").schema,
+                json!({"y": {"c": 1, "b": 2}}),
+            ))
+        });
+
+        let letters = Letters(('a'..='z').zip(0..).collect());
+        tracker.track(TrackedEvent::new(letters)).await.unwrap();
+
+        let event = &sink.events()[0];
+        let expected: String = ('a'..='z')
+            .zip(0..)
+            .map(|(letter, n)| format!("\"{letter}\":{n}"))
+            .collect::<Vec<_>>()
+            .join(",");
+        assert!(event["ue_pr"].as_str().unwrap().contains(&expected));
+
+        let co = event["co"].as_str().unwrap();
+        assert!(co.contains(r#"{"a":2,"z":1}"#));
+        assert!(co.contains(r#"{"y":{"b":2,"c":1}}"#));
+    }
+
+    #[tokio::test]
+    async fn stringify_bools_covers_payloads_and_contexts() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            stringify_bools: true,
+            platform_contexts: HashMap::from([(
+                Platform::default(),
+                vec![SelfDescribingJson::new(
+                    entity("platform").schema,
+                    json!({"enabled": true}),
+                )],
+            )]),
+            ..testing::config()
+        };
+        let mut tracker = Tracker::new(&sink, config).unwrap();
+        tracker.add_context_provider(|| {
+            Some(SelfDescribingJson::new(
+                entity("provided").schema,
+                json!({"flags": [false, true]}),
+            ))
+        });
+
+        tracker
+            .track_raw(
+                "iglu:com.example/raw/jsonschema/1-0-0",
+                json!({"nested": {"ok": true}, "count": 1}),
+            )
+            .await
+            .unwrap();
+
+        let event = &sink.events()[0];
+        assert_eq!(
+            unstruct_data(event)["data"],
+            json!({"nested": {"ok": "1"}, "count": 1})
+        );
+
+        let contexts = contexts_of(event);
+        assert_eq!(contexts[0]["data"], json!({"enabled": "1"}));
+        assert_eq!(contexts[1]["data"], json!({"flags": ["0", "1"]}));
+    }
+
+    /// A payload whose serialization fails if `fail` is set
+    #[derive(Debug, Clone)]
+    struct Fallible {
+        id: u32,
+        fail: bool,
+    }
+
+    impl Serialize for Fallible {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self.fail {
+                true => Err(serde::ser::Error::custom("unserializable")),
+                false => TestEvent { id: self.id }.serialize(serializer),
+            }
+        }
+    }
+
+    impl HasSchema for Fallible {
+        fn schema(&self) -> Schema {
+            TestEvent { id: self.id }.schema()
+        }
+    }
+
+    fn fallible_batch() -> Vec<TrackedEvent<Fallible>> {
+        [false, true, false]
+            .into_iter()
+            .enumerate()
+            .map(|(id, fail)| {
+                TrackedEvent::new(Fallible {
+                    id: id as u32,
+                    fail,
+                })
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn unserializable_event_fails_batch_by_default() {
+        let sink = RecordingSink::default();
+        let tracker = Tracker::new(&sink, testing::config()).unwrap();
+
+        assert!(tracker.track_batch(fallible_batch()).await.is_err());
+        assert!(sink.batches().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unserializable_events_can_be_dropped() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            drop_unserializable_events: true,
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        let summary = tracker.track_batch(fallible_batch()).await.unwrap();
+        assert_eq!(
+            summary,
+            TrackBatchSummary {
+                sent: 2,
+                unserializable: 1,
+                ..TrackBatchSummary::default()
+            }
+        );
+
+        let event = TrackedEvent::new(Fallible { id: 3, fail: true });
+        assert_eq!(
+            tracker.track(event).await.unwrap(),
+            Tracked::Dropped(DropReason::Unserializable)
+        );
+
+        let ids: Vec<_> = sink
+            .events()
+            .iter()
+            .map(|event| unstruct_data(event)["data"]["id"].as_u64().unwrap())
+            .collect();
+        assert_eq!(ids, [0, 2]);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn metrics_count_sent_dropped_and_failed_events() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let _guard = metrics::set_default_local_recorder(&recorder);
+
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            drop_unserializable_events: true,
+            dedup_window: Some(Duration::from_secs(60)),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        // Two events are sent, and one is dropped as unserializable
+        tracker.track_batch(fallible_batch()).await.unwrap();
+
+        // One event is dropped as a duplicate
+        let event = TrackedEvent::new(TestEvent { id: 7 });
+        tracker.track(event.clone()).await.unwrap();
+        tracker.track(event).await.unwrap();
+
+        // Two events fail to send
+        sink.set_failing(true);
+        let batch = (10..12).map(|id| TrackedEvent::new(TestEvent { id }));
+        assert!(tracker.track_batch(batch).await.is_err());
+
+        let snapshot = snapshotter.snapshot().into_hashmap();
+        let value = |name: &'static str| {
+            snapshot
+                .iter()
+                .find(|(key, _)| key.key().name() == name)
+                .map(|(_, (_, _, value))| value)
+        };
+        let counter = |name| match value(name) {
+            Some(DebugValue::Counter(count)) => *count,
+            other => panic!("{name} isn't a counter: {other:?}"),
+        };
+
+        assert_eq!(counter("snowplow.events.sent"), 3);
+        assert_eq!(counter("snowplow.events.dropped"), 2);
+        assert_eq!(counter("snowplow.events.failed"), 2);
+        match value("snowplow.flush.duration") {
+            Some(DebugValue::Histogram(durations)) => assert_eq!(durations.len(), 3),
+            other => panic!("flush.duration isn't a histogram: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn track_many_splits_into_batches() {
+        let sink = RecordingSink::default();
+        let tracker = Tracker::new(&sink, testing::config()).unwrap();
+
+        let events = (0..10_000).map(|id| TrackedEvent::new(TestEvent { id }));
+        let summary = tracker
+            .track_many(events, NonZeroUsize::new(300).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            summary,
+            TrackManySummary {
+                events: 10_000,
+                dropped: 0,
+                batches: 34
+            }
+        );
+        assert_eq!(sink.batches().len(), 34);
+        assert_eq!(sink.events().len(), 10_000);
+    }
+
+    #[tokio::test]
+    async fn disabled_tracker_drops_every_event() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            disabled: true,
+            dedup_window: Some(Duration::from_secs(60)),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        let summary = tracker
+            .track_batch((0..3).map(|id| TrackedEvent::new(TestEvent { id })))
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            TrackBatchSummary {
+                disabled: 3,
+                ..TrackBatchSummary::default()
+            }
+        );
+        assert_eq!(
+            tracker
+                .track_raw("iglu:com.example/raw/jsonschema/1-0-0", json!({}))
+                .await
+                .unwrap(),
+            Tracked::Dropped(DropReason::Disabled)
+        );
+        assert!(sink.batches().is_empty());
+    }
+
+    #[tokio::test]
+    async fn track_many_counts_dropped_events() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            dedup_window: Some(Duration::from_secs(60)),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+
+        let events = (0..10).map(|id| TrackedEvent::new(TestEvent { id: id % 4 }));
+        let summary = tracker
+            .track_many(events, NonZeroUsize::new(3).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            summary,
+            TrackManySummary {
+                events: 10,
+                dropped: 6,
+                batches: 4
+            }
+        );
+        assert_eq!(sink.events().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn track_many_reports_what_was_delivered() {
+        let sink = RecordingSink::default();
+        let tracker = Tracker::new(&sink, testing::config()).unwrap();
+
+        let events = (0..7).map(|id| TrackedEvent::new(Fallible { id, fail: id == 4 }));
+        let err = tracker
+            .track_many(events, NonZeroUsize::new(3).unwrap())
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            err.delivered,
+            TrackManySummary {
+                events: 3,
+                dropped: 0,
+                batches: 1
+            }
+        );
+        assert_eq!(sink.events().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn schema_rate_warning_once_per_interval() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            schema_rate_warning: Some(RateThreshold {
+                events: 3,
+                interval: Duration::from_millis(200),
+            }),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+        let (_guard, warnings) = capture_warnings();
+
+        let batch = || (0..10).map(|id| TrackedEvent::new(TestEvent { id }));
+        tracker.track_batch(batch()).await.unwrap();
+        tracker.track_batch(batch()).await.unwrap();
+        assert_eq!(warnings.messages().len(), 1);
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        tracker.track_batch(batch()).await.unwrap();
+        assert_eq!(warnings.messages().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn schema_rate_warning_with_maximum_threshold() {
+        let sink = RecordingSink::default();
+        let config = TrackerConfig {
+            schema_rate_warning: Some(RateThreshold {
+                events: u64::MAX,
+                interval: Duration::from_secs(60),
+            }),
+            ..testing::config()
+        };
+        let tracker = Tracker::new(&sink, config).unwrap();
+        let (_guard, warnings) = capture_warnings();
+
+        let batch = (0..10).map(|id| TrackedEvent::new(TestEvent { id }));
+        tracker.track_batch(batch).await.unwrap();
+        assert!(warnings.messages().is_empty());
     }
 }
@@ -6,13 +6,16 @@ event containers.
 
 use std::cell::Cell;
 use std::fmt::{Display, Write as _};
+use std::io;
 
 use lazy_format::lazy_format;
-use serde::ser;
-use serde_json::to_string;
+use serde::ser::{self, SerializeMap as _, SerializeSeq as _};
+use serde_json::{to_string, Value};
+
+use crate::payload::{HasSchema, Schema};
 
 thread_local! {
-    static STRINGIFY_BUFFER: Cell<String> = Cell::new(String::new());
+    static STRINGIFY_BUFFER: Cell<String> = const { Cell::new(String::new()) };
 }
 
 /// Adapter type that serializes something by converting it into a string and
@@ -61,3 +64,313 @@ impl<T: ser::Serialize> ser::Serialize for JsonString<T> {
         serializer.serialize_str(&jsonified)
     }
 }
+
+/// Adapter type that serializes something with the keys of all of its maps
+/// sorted, including nested ones. Struct fields are serialized in their
+/// declared order, which is already stable, but maps like `HashMap` have no
+/// stable order; wrap an event payload in this to produce byte-identical JSON
+/// for the same logical event, for snapshot tests or hashing. This implements
+/// [`HasSchema`] by forwarding to the wrapped value, so it can be used as a
+/// [`TrackedEvent`][crate::tracker::TrackedEvent] payload directly.
+///
+/// This is a bit slower than serializing the value directly, because the value
+/// is first converted to a [`serde_json::Value`]. To sort the keys of every
+/// event a tracker sends, including its context entities, enable
+/// [`TrackerConfig::sort_keys`][crate::TrackerConfig::sort_keys] instead.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use snowplow_tracker::util::SortedKeys;
+///
+/// let map: HashMap<_, _> = [("b", 2), ("c", 3), ("a", 1)].into_iter().collect();
+/// let json = serde_json::to_string(&SortedKeys(map)).unwrap();
+/// assert_eq!(json, r#"{"a":1,"b":2,"c":3}"#);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SortedKeys<T>(pub T);
+
+impl<T: ser::Serialize> ser::Serialize for SortedKeys<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let value = serde_json::to_value(&self.0).map_err(|json_err| {
+            ser::Error::custom(lazy_format!("Error converting to JSON value: {json_err}"))
+        })?;
+
+        SortedValue(&value).serialize(serializer)
+    }
+}
+
+impl<T: HasSchema> HasSchema for SortedKeys<T> {
+    fn schema(&self) -> Schema {
+        self.0.schema()
+    }
+}
+
+/// Serializes a JSON value with sorted object keys. `serde_json` maps are
+/// already sorted unless its `preserve_order` feature is enabled somewhere in
+/// the dependency graph, so this sorts explicitly rather than relying on that.
+struct SortedValue<'a>(&'a Value);
+
+impl ser::Serialize for SortedValue<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self.0 {
+            Value::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(&SortedValue(item))?;
+                }
+                seq.end()
+            }
+            Value::Object(object) => {
+                let mut entries: Vec<_> = object.iter().collect();
+                entries.sort_unstable_by_key(|&(key, _)| key);
+
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, &SortedValue(value))?;
+                }
+                map.end()
+            }
+            value => value.serialize(serializer),
+        }
+    }
+}
+
+/// Adapter type that serializes something with all of its booleans, including
+/// nested ones, replaced with the strings `"1"` and `"0"`. This is for
+/// interoperating with legacy collectors or enrichments that expect booleans
+/// in that form. Like [`SortedKeys`], this implements [`HasSchema`] by
+/// forwarding to the wrapped value, so it can wrap an event payload, or an
+/// entity passed to
+/// [`SelfDescribingJson::from_entity`][crate::payload::SelfDescribingJson::from_entity].
+/// To stringify the booleans of every event a tracker sends, including its
+/// context entities, enable
+/// [`TrackerConfig::stringify_bools`][crate::TrackerConfig::stringify_bools]
+/// instead.
+///
+/// ```
+/// use snowplow_tracker::util::StringifyBools;
+///
+/// let json = serde_json::to_string(&StringifyBools([true, false])).unwrap();
+/// assert_eq!(json, r#"["1","0"]"#);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StringifyBools<T>(pub T);
+
+impl<T: ser::Serialize> ser::Serialize for StringifyBools<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut value = serde_json::to_value(&self.0).map_err(|json_err| {
+            ser::Error::custom(lazy_format!("Error converting to JSON value: {json_err}"))
+        })?;
+
+        stringify_bools(&mut value);
+        value.serialize(serializer)
+    }
+}
+
+impl<T: HasSchema> HasSchema for StringifyBools<T> {
+    fn schema(&self) -> Schema {
+        self.0.schema()
+    }
+}
+
+fn stringify_bools(value: &mut Value) {
+    match value {
+        Value::Bool(flag) => *value = Value::String(if *flag { "1" } else { "0" }.to_owned()),
+        Value::Array(items) => items.iter_mut().for_each(stringify_bools),
+        Value::Object(object) => object.values_mut().for_each(stringify_bools),
+        _ => {}
+    }
+}
+
+/// How the tracker encodes event payloads and context entities as JSON, as
+/// set by [`TrackerConfig::sort_keys`][crate::TrackerConfig::sort_keys],
+/// [`TrackerConfig::stringify_bools`][crate::TrackerConfig::stringify_bools]
+/// and, for the ecommerce schemas that have a currency,
+/// [`TrackerConfig::default_currency`][crate::TrackerConfig::default_currency].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct JsonEncoding<'a> {
+    pub sort_keys: bool,
+    pub stringify_bools: bool,
+    pub default_currency: Option<&'a str>,
+}
+
+impl JsonEncoding<'_> {
+    /// Encode a JSON value in place, like the data of a context entity.
+    pub fn apply(self, value: &mut Value) {
+        if let (Some(currency), Value::Object(object)) = (self.default_currency, &mut *value) {
+            let currency_field = object.entry("currency").or_insert(Value::Null);
+            if matches!(currency_field, Value::Null) || *currency_field == "" {
+                *currency_field = Value::String(currency.to_owned());
+            }
+        }
+        if self.stringify_bools {
+            stringify_bools(value);
+        }
+        if self.sort_keys {
+            sort_keys(value);
+        }
+    }
+}
+
+/// Sorts the keys of every object in a JSON value. Without the
+/// `preserve_order` feature of `serde_json` its maps are always sorted, and
+/// with it they keep the sorted insertion order.
+fn sort_keys(value: &mut Value) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(sort_keys),
+        Value::Object(object) => {
+            let mut entries: Vec<_> = std::mem::take(object).into_iter().collect();
+            entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+            for (key, mut value) in entries {
+                sort_keys(&mut value);
+                object.insert(key, value);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Adapter type that serializes an event payload with a [`JsonEncoding`],
+/// and forwards [`HasSchema`] to it.
+#[derive(Debug, Clone)]
+pub(crate) struct Encoded<'a, T> {
+    pub value: T,
+    pub encoding: JsonEncoding<'a>,
+}
+
+impl<T: ser::Serialize> ser::Serialize for Encoded<'_, T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        if self.encoding == JsonEncoding::default() {
+            return self.value.serialize(serializer);
+        }
+
+        let mut value = serde_json::to_value(&self.value).map_err(|json_err| {
+            ser::Error::custom(lazy_format!("Error converting to JSON value: {json_err}"))
+        })?;
+
+        self.encoding.apply(&mut value);
+        value.serialize(serializer)
+    }
+}
+
+impl<T: HasSchema> HasSchema for Encoded<'_, T> {
+    fn schema(&self) -> Schema {
+        self.value.schema()
+    }
+}
+
+/// A writer that discards everything written to it, but counts the bytes.
+/// Useful for measuring the serialized size of something without allocating.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ByteCounter(pub usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Serialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::payload::SchemaVersion;
+
+    #[derive(Serialize)]
+    struct Nested {
+        inner: HashMap<&'static str, u32>,
+        flags: Vec<bool>,
+    }
+
+    impl HasSchema for Nested {
+        fn schema(&self) -> Schema {
+            Schema::new("com.example", "nested", SchemaVersion::new(1, 0, 0))
+        }
+    }
+
+    fn nested() -> Nested {
+        Nested {
+            inner: [("z", 1), ("m", 2), ("a", 3)].into_iter().collect(),
+            flags: vec![true, false],
+        }
+    }
+
+    #[test]
+    fn stringify_serializes_display() {
+        assert_eq!(serde_json::to_string(&Stringify(42)).unwrap(), r#""42""#);
+    }
+
+    #[test]
+    fn json_string_serializes_json_as_string() {
+        let json = serde_json::to_string(&JsonString(json!({"a": 1}))).unwrap();
+        assert_eq!(json, r#""{\"a\":1}""#);
+    }
+
+    #[test]
+    fn sorted_keys_sorts_nested_maps() {
+        let json = serde_json::to_string(&SortedKeys(nested())).unwrap();
+        assert_eq!(
+            json,
+            r#"{"flags":[true,false],"inner":{"a":3,"m":2,"z":1}}"#
+        );
+    }
+
+    #[test]
+    fn sorted_keys_is_deterministic() {
+        let first = serde_json::to_string(&SortedKeys(nested())).unwrap();
+        for _ in 0..10 {
+            assert_eq!(serde_json::to_string(&SortedKeys(nested())).unwrap(), first);
+        }
+    }
+
+    #[test]
+    fn stringify_bools_replaces_nested_bools() {
+        let value = serde_json::to_value(StringifyBools(json!({
+            "enabled": true,
+            "nested": {"flags": [false, true]},
+            "count": 1,
+        })))
+        .unwrap();
+
+        assert_eq!(
+            value,
+            json!({"enabled": "1", "nested": {"flags": ["0", "1"]}, "count": 1})
+        );
+    }
+
+    #[test]
+    fn adapters_forward_schema() {
+        let schema = nested().schema();
+        assert_eq!(SortedKeys(nested()).schema(), schema);
+        assert_eq!(StringifyBools(nested()).schema(), schema);
+    }
+
+    #[test]
+    fn byte_counter_counts_serialized_bytes() {
+        let mut counter = ByteCounter::default();
+        serde_json::to_writer(&mut counter, &json!({"a": [1, 2]})).unwrap();
+        assert_eq!(counter.0, r#"{"a":[1,2]}"#.len());
+    }
+}